@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use butterfly_effect::{Action, Bindings, SaveData};
+
+/// The keys that can be bound to an action. Return and Q are left out, the
+/// pause and game over screens use them next to the bound actions.
+const BINDABLE_KEYS: [KeyCode; 44] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Space,
+    KeyCode::Escape,
+    KeyCode::Back,
+    KeyCode::Tab,
+    KeyCode::LShift,
+];
+
+/// The name a key is stored under in the save data.
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+/// A short name of a key to show on screen, from the name it is stored
+/// under.
+pub fn label(name: &str) -> String {
+    name.trim_start_matches("Key").to_uppercase()
+}
+
+/// A key that was just pressed and can be bound to an action.
+pub fn just_pressed_key(keyboard_input: &Input<KeyCode>) -> Option<KeyCode> {
+    BINDABLE_KEYS
+        .iter()
+        .copied()
+        .find(|&key| keyboard_input.just_pressed(key))
+}
+
+/// The key bindings of the settings as key codes.
+#[derive(Default)]
+pub struct ActionMap(HashMap<Action, Vec<KeyCode>>);
+
+impl ActionMap {
+    fn new(bindings: &Bindings) -> Self {
+        let mut keys = HashMap::default();
+        for &action in Action::ALL.iter() {
+            let codes = bindings
+                .keys(action)
+                .iter()
+                .filter_map(|name| {
+                    let code = BINDABLE_KEYS
+                        .iter()
+                        .copied()
+                        .find(|&key| key_name(key) == *name);
+                    if code.is_none() {
+                        warn!("ignoring unknown key {} bound to {}", name, action.name());
+                    }
+                    code
+                })
+                .collect();
+            keys.insert(action, codes);
+        }
+        ActionMap(keys)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn just_pressed(&self, keyboard_input: &Input<KeyCode>, action: Action) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|&key| keyboard_input.just_pressed(key))
+    }
+
+    /// Like [`Input::reset`] for every key of `action`, so the screen entered
+    /// next does not see the same press.
+    pub fn reset(&self, keyboard_input: &mut Input<KeyCode>, action: Action) {
+        for &key in self.keys(action) {
+            keyboard_input.reset(key);
+        }
+    }
+
+    /// The label of the first key bound to `action`, `-` for none.
+    pub fn label(&self, action: Action) -> String {
+        match self.keys(action).first() {
+            Some(&key) => label(&key_name(key)),
+            None => "-".to_string(),
+        }
+    }
+}
+
+/// Rebuilds the [`ActionMap`] whenever the settings change.
+pub fn update_action_map(save: Res<SaveData>, mut action_map: ResMut<ActionMap>) {
+    if save.is_changed() {
+        *action_map = ActionMap::new(&save.settings.bindings);
+    }
+}
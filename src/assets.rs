@@ -0,0 +1,79 @@
+use bevy::asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+
+use butterfly_effect::level::{Level, Manifest};
+
+#[derive(Debug, TypeUuid)]
+#[uuid = "d356b976-5b6d-4568-b719-484db176f081"]
+pub struct LevelData {
+    pub level: Level,
+}
+
+/// The ordered list of levels and the screen shown after the last one.
+#[derive(Debug, TypeUuid)]
+#[uuid = "4470110e-052d-44f5-8a94-a4b3e726b69f"]
+pub struct LevelManifest {
+    pub levels: Vec<Handle<LevelData>>,
+    pub you_won: Handle<LevelData>,
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let level = Level::parse(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(LevelData { level }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+#[derive(Default)]
+pub struct LevelManifestLoader;
+
+impl AssetLoader for LevelManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let manifest = Manifest::parse(std::str::from_utf8(bytes)?)?;
+            let directory = load_context.path().parent().unwrap().to_path_buf();
+            let mut dependencies = vec![];
+            let mut level_handle = |file: &str| {
+                let path = AssetPath::new(directory.join(file), None);
+                let handle: Handle<LevelData> = load_context.get_handle(path.get_id());
+                dependencies.push(path);
+                handle
+            };
+
+            let levels = manifest
+                .levels
+                .iter()
+                .map(|file| level_handle(file))
+                .collect();
+            let you_won = level_handle(&manifest.you_won);
+            load_context.set_default_asset(
+                LoadedAsset::new(LevelManifest { levels, you_won }).with_dependencies(dependencies),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["manifest"]
+    }
+}
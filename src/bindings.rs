@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Direction;
+
+/// Something the player can do during a level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Reset,
+    Undo,
+    Hint,
+    Pause,
+}
+
+impl Action {
+    /// Every action, in the order the controls screen lists them.
+    pub const ALL: [Action; 8] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Reset,
+        Action::Undo,
+        Action::Hint,
+        Action::Pause,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Reset => "reset",
+            Action::Undo => "undo",
+            Action::Hint => "hint",
+            Action::Pause => "pause",
+        }
+    }
+
+    /// The direction a steering action points the rocket in.
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            Action::Up => Some(Direction::Up),
+            Action::Down => Some(Direction::Down),
+            Action::Left => Some(Direction::Left),
+            Action::Right => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Up => &["Up", "W"],
+            Action::Down => &["Down", "S"],
+            Action::Left => &["Left", "A"],
+            Action::Right => &["Right", "D"],
+            Action::Reset => &["R"],
+            Action::Undo => &["U"],
+            Action::Hint => &["H"],
+            Action::Pause => &["Escape"],
+        }
+    }
+}
+
+/// The keys bound to every action. Keys are stored by the name of their
+/// Bevy `KeyCode`, so the save data does not depend on Bevy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bindings(BTreeMap<Action, Vec<String>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings(
+            Action::ALL
+                .iter()
+                .map(|&action| {
+                    let keys = action.default_keys().iter().map(|key| key.to_string());
+                    (action, keys.collect())
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Bindings {
+    /// The keys bound to `action`, which may be none.
+    pub fn keys(&self, action: Action) -> &[String] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Binds `key` to `action` alone and takes it away from any other
+    /// action, so a key never does two things at once. An action left
+    /// without keys that way gets the keys `action` had instead.
+    pub fn rebind(&mut self, action: Action, key: &str) {
+        let previous = self
+            .0
+            .insert(action, vec![key.to_string()])
+            .unwrap_or_default();
+        for (&other, keys) in self.0.iter_mut() {
+            if other == action || !keys.iter().any(|bound| bound == key) {
+                continue;
+            }
+            keys.retain(|bound| bound != key);
+            if keys.is_empty() {
+                *keys = previous
+                    .iter()
+                    .filter(|bound| *bound != key)
+                    .cloned()
+                    .collect();
+            }
+        }
+    }
+}
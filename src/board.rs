@@ -0,0 +1,337 @@
+use petgraph::algo::dijkstra;
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use crate::level::Level;
+
+/// Hits a cracked `X` wall takes before it crumbles. A `x` crumbles on the
+/// first one.
+pub const CRACKED_WALL_HITS: u8 = 3;
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    /// The neighbouring position in `direction`.
+    pub fn step(self, direction: Direction) -> Self {
+        match direction {
+            Direction::Left => Position {
+                x: self.x - 1,
+                y: self.y,
+            },
+            Direction::Right => Position {
+                x: self.x + 1,
+                y: self.y,
+            },
+            Direction::Up => Position {
+                x: self.x,
+                y: self.y + 1,
+            },
+            Direction::Down => Position {
+                x: self.x,
+                y: self.y - 1,
+            },
+            Direction::StandStill => self,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Direction {
+    Left,
+    Up,
+    Right,
+    Down,
+    StandStill,
+}
+
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::StandStill => Self::StandStill,
+        }
+    }
+}
+
+/// The two diagonal mirrors, named after how they are drawn in a level.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Deflector {
+    /// `/`
+    Slash,
+    /// `\`
+    Backslash,
+}
+
+impl Deflector {
+    /// The direction a rocket moving in `direction` leaves the mirror in.
+    pub fn deflect(self, direction: Direction) -> Direction {
+        match (self, direction) {
+            (Deflector::Slash, Direction::Right) => Direction::Up,
+            (Deflector::Slash, Direction::Up) => Direction::Right,
+            (Deflector::Slash, Direction::Left) => Direction::Down,
+            (Deflector::Slash, Direction::Down) => Direction::Left,
+            (Deflector::Backslash, Direction::Right) => Direction::Down,
+            (Deflector::Backslash, Direction::Down) => Direction::Right,
+            (Deflector::Backslash, Direction::Left) => Direction::Up,
+            (Deflector::Backslash, Direction::Up) => Direction::Left,
+            (_, Direction::StandStill) => Direction::StandStill,
+        }
+    }
+}
+
+/// The colour of a key and the doors it opens.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+pub enum KeyColor {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Target,
+    /// Moves the rocket to the other teleporter with the same number,
+    /// keeping its direction.
+    Teleporter(u8),
+    /// Can only be entered moving in this direction, a wall otherwise.
+    Gate(Direction),
+    /// Turns the rocket by 90 degrees, without costing a turn or leaving a
+    /// wall behind.
+    Deflector(Deflector),
+    /// A wall that crumbles after this many more hits.
+    Cracked(u8),
+    /// Picked up by the rocket when it moves over it. Every run starts
+    /// without keys.
+    Key(KeyColor),
+    /// A wall for runs that did not pick up the key of the same colour.
+    Door(KeyColor),
+}
+
+/// The tiles of a level, including its border walls. `(0, 0)` is the bottom
+/// left corner.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Board {
+    width: u32,
+    height: u32,
+    tiles: Vec<Tile>,
+    start: Position,
+}
+
+impl Board {
+    pub fn new(width: u32, height: u32, start: Position) -> Self {
+        Board {
+            width,
+            height,
+            tiles: vec![Tile::Empty; (width * height) as usize],
+            start,
+        }
+    }
+
+    /// Surrounds the level grid with border walls.
+    pub fn from_level(level: &Level) -> Self {
+        let mut board = Board::new(level.width + 2, level.height + 2, Position::default());
+        for x in 0..board.width as i32 {
+            board.set_tile(Position { x, y: 0 }, Tile::Wall);
+            board.set_tile(
+                Position {
+                    x,
+                    y: board.height as i32 - 1,
+                },
+                Tile::Wall,
+            );
+        }
+        for y in 0..board.height as i32 {
+            board.set_tile(Position { x: 0, y }, Tile::Wall);
+            board.set_tile(
+                Position {
+                    x: board.width as i32 - 1,
+                    y,
+                },
+                Tile::Wall,
+            );
+        }
+
+        for (y, row) in level.grid.iter().rev().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let pos = Position {
+                    x: x as i32 + 1,
+                    y: y as i32 + 1,
+                };
+                match c {
+                    'W' => board.set_tile(pos, Tile::Wall),
+                    'T' => board.set_tile(pos, Tile::Target),
+                    '1'..='9' => board.set_tile(pos, Tile::Teleporter(c as u8 - b'0')),
+                    '<' => board.set_tile(pos, Tile::Gate(Direction::Left)),
+                    '>' => board.set_tile(pos, Tile::Gate(Direction::Right)),
+                    '^' => board.set_tile(pos, Tile::Gate(Direction::Up)),
+                    'v' => board.set_tile(pos, Tile::Gate(Direction::Down)),
+                    'X' => board.set_tile(pos, Tile::Cracked(CRACKED_WALL_HITS)),
+                    'x' => board.set_tile(pos, Tile::Cracked(1)),
+                    'r' => board.set_tile(pos, Tile::Key(KeyColor::Red)),
+                    'g' => board.set_tile(pos, Tile::Key(KeyColor::Green)),
+                    'b' => board.set_tile(pos, Tile::Key(KeyColor::Blue)),
+                    'y' => board.set_tile(pos, Tile::Key(KeyColor::Yellow)),
+                    'R' => board.set_tile(pos, Tile::Door(KeyColor::Red)),
+                    'G' => board.set_tile(pos, Tile::Door(KeyColor::Green)),
+                    'B' => board.set_tile(pos, Tile::Door(KeyColor::Blue)),
+                    'Y' => board.set_tile(pos, Tile::Door(KeyColor::Yellow)),
+                    '/' => board.set_tile(pos, Tile::Deflector(Deflector::Slash)),
+                    '\\' => board.set_tile(pos, Tile::Deflector(Deflector::Backslash)),
+                    'S' | 's' => board.start = pos,
+                    _ => {}
+                }
+            }
+        }
+        board
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x >= 0 && pos.y >= 0 && (pos.x as u32) < self.width && (pos.y as u32) < self.height
+    }
+
+    /// Everything outside of the board counts as a wall.
+    pub fn tile(&self, pos: Position) -> Tile {
+        if self.contains(pos) {
+            self.tiles[self.index(pos)]
+        } else {
+            Tile::Wall
+        }
+    }
+
+    pub fn set_tile(&mut self, pos: Position, tile: Tile) {
+        if self.contains(pos) {
+            let index = self.index(pos);
+            self.tiles[index] = tile;
+        }
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = (Position, Tile)> + '_ {
+        self.tiles.iter().enumerate().map(move |(index, &tile)| {
+            let pos = Position {
+                x: (index as u32 % self.width) as i32,
+                y: (index as u32 / self.width) as i32,
+            };
+            (pos, tile)
+        })
+    }
+
+    pub fn targets(&self) -> impl Iterator<Item = Position> + '_ {
+        self.tiles()
+            .filter(|&(_, tile)| tile == Tile::Target)
+            .map(|(pos, _)| pos)
+    }
+
+    /// Whether the rocket can move onto `pos` while moving in `direction`,
+    /// without any keys.
+    pub fn can_enter(&self, pos: Position, direction: Direction) -> bool {
+        match self.tile(pos) {
+            Tile::Wall | Tile::Cracked(_) | Tile::Door(_) => false,
+            Tile::Gate(gate) => gate == direction,
+            _ => true,
+        }
+    }
+
+    /// Damages the cracked wall on `pos`, which turns into an empty tile on
+    /// its last hit. Returns whether there was a cracked wall.
+    pub fn hit(&mut self, pos: Position) -> bool {
+        match self.tile(pos) {
+            Tile::Cracked(1) => self.set_tile(pos, Tile::Empty),
+            Tile::Cracked(hits) => self.set_tile(pos, Tile::Cracked(hits - 1)),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Takes back a [`Board::hit`] on `pos`.
+    pub fn repair(&mut self, pos: Position) {
+        match self.tile(pos) {
+            Tile::Empty => self.set_tile(pos, Tile::Cracked(1)),
+            Tile::Cracked(hits) => self.set_tile(pos, Tile::Cracked(hits + 1)),
+            _ => {}
+        }
+    }
+
+    /// The other end of the teleporter on `pos`.
+    pub fn partner(&self, pos: Position) -> Option<Position> {
+        match self.tile(pos) {
+            Tile::Teleporter(number) => self
+                .tiles()
+                .find(|&(other, tile)| other != pos && tile == Tile::Teleporter(number))
+                .map(|(other, _)| other),
+            _ => None,
+        }
+    }
+
+    /// Whether any target can be reached from `from` by walking through
+    /// tiles the rocket may enter, ignoring how the rocket actually moves.
+    /// Cracked walls count as broken and doors as open. The rocket can always
+    /// leave `from`, even if that is a wall.
+    pub fn target_reachable(&self, from: Position) -> bool {
+        if !self.contains(from) {
+            return false;
+        }
+
+        // Every tile is a node, numbered like `tiles`.
+        let mut g = DiGraph::<(), ()>::with_capacity(self.tiles.len(), 0);
+        for _ in 0..self.tiles.len() {
+            g.add_node(());
+        }
+        for (here, _) in self.tiles() {
+            if here != from && !self.passable(here) {
+                continue;
+            }
+            for &direction in &[
+                Direction::Left,
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+            ] {
+                let neighbour = here.step(direction);
+                let opens = matches!(self.tile(neighbour), Tile::Cracked(_) | Tile::Door(_));
+                if opens || self.can_enter(neighbour, direction) {
+                    g.add_edge(self.node(here), self.node(neighbour), ());
+                }
+            }
+            if let Some(partner) = self.partner(here) {
+                g.add_edge(self.node(here), self.node(partner), ());
+            }
+        }
+
+        let node_map = dijkstra(&g, self.node(from), None, |_| 1);
+        self.targets()
+            .any(|target| node_map.contains_key(&self.node(target)))
+    }
+
+    fn passable(&self, pos: Position) -> bool {
+        self.tile(pos) != Tile::Wall
+    }
+
+    fn node(&self, pos: Position) -> NodeIndex {
+        NodeIndex::new(self.index(pos))
+    }
+
+    fn index(&self, pos: Position) -> usize {
+        (pos.x as u32 + pos.y as u32 * self.width) as usize
+    }
+}
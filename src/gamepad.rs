@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use butterfly_effect::Direction;
+
+use crate::{InputBuffer, LevelInfo, ResetEvent};
+
+/// How far the left stick has to be pushed to count as a direction.
+const STICK_DEADZONE: f32 = 0.5;
+
+/// The connected gamepads and the direction each left stick points in.
+#[derive(Default)]
+pub struct Gamepads(HashMap<Gamepad, Option<Direction>>);
+
+pub fn gamepad_connections(mut gamepads: ResMut<Gamepads>, mut reader: EventReader<GamepadEvent>) {
+    for GamepadEvent(gamepad, event) in reader.iter() {
+        match event {
+            GamepadEventType::Connected => {
+                info!("gamepad {} connected", gamepad.0);
+                gamepads.0.insert(*gamepad, None);
+            }
+            GamepadEventType::Disconnected => {
+                info!("gamepad {} disconnected", gamepad.0);
+                gamepads.0.remove(gamepad);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn stick_direction(x: f32, y: f32) -> Option<Direction> {
+    if x.abs().max(y.abs()) < STICK_DEADZONE {
+        None
+    } else if x.abs() > y.abs() {
+        Some(if x > 0.0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        })
+    } else {
+        Some(if y > 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        })
+    }
+}
+
+/// The D-pad and the left stick steer like the direction keys, the south
+/// face button resets the run. The stick steers once each time it is pushed
+/// out of the deadzone or into a new direction.
+pub fn gamepad_input(
+    mut gamepads: ResMut<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    level_info: Res<LevelInfo>,
+    mut input_buffer: ResMut<InputBuffer>,
+    mut reset_writer: EventWriter<ResetEvent>,
+) {
+    if !level_info.loaded {
+        return;
+    }
+    let dpad = [
+        (GamepadButtonType::DPadLeft, Direction::Left),
+        (GamepadButtonType::DPadDown, Direction::Down),
+        (GamepadButtonType::DPadUp, Direction::Up),
+        (GamepadButtonType::DPadRight, Direction::Right),
+    ];
+    for (&gamepad, stick) in gamepads.0.iter_mut() {
+        for &(button, dir) in dpad.iter() {
+            if buttons.just_pressed(GamepadButton(gamepad, button)) {
+                input_buffer.push(dir);
+            }
+        }
+
+        let x = axes
+            .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.0);
+        let y = axes
+            .get(GamepadAxis(gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.0);
+        let dir = stick_direction(x, y);
+        if dir != *stick {
+            if let Some(dir) = dir {
+                input_buffer.push(dir);
+            }
+            *stick = dir;
+        }
+
+        if buttons.just_pressed(GamepadButton(gamepad, GamepadButtonType::South)) {
+            reset_writer.send(ResetEvent {});
+        }
+    }
+}
@@ -0,0 +1,168 @@
+const DEFAULT_TURNS: u32 = 10;
+const DEFAULT_RUNS: u32 = 3;
+
+/// A level file: a `key: value` header, a `---` line and the tile grid.
+///
+/// `turns` is the turn budget of every run and `runs` the number of runs
+/// that have to reach a target to complete the level. `par` is the score
+/// that earns three stars, see [`Score`](crate::score::Score); it defaults to
+/// the full turn budget of all runs. `width` and `height`
+/// give the size of the grid inside the border walls; they default to the
+/// longest row and the number of rows. Short rows are padded with empty
+/// tiles on the right, missing rows are added at the top.
+///
+/// ```text
+/// name: Pillars
+/// width: 22
+/// height: 14
+/// turns: 10
+/// runs: 3
+/// par: 45
+/// ---
+///           WWWW       T
+/// s WWWWW          WWWWW
+/// ```
+#[derive(Debug, Clone)]
+pub struct Level {
+    pub name: String,
+    pub turns: u32,
+    pub runs: u32,
+    pub par: u32,
+    pub width: u32,
+    pub height: u32,
+    pub grid: Vec<String>,
+}
+
+impl Level {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut lines = source.lines();
+        let mut name = None;
+        let mut turns = DEFAULT_TURNS;
+        let mut runs = DEFAULT_RUNS;
+        let mut par = None;
+        let mut width = None;
+        let mut height = None;
+
+        for line in &mut lines {
+            let line = line.trim();
+            if line == "---" {
+                break;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match split_entry(line)? {
+                ("name", value) => name = Some(value.to_string()),
+                ("turns", value) => turns = value.parse()?,
+                ("runs", value) => runs = value.parse()?,
+                ("par", value) => par = Some(value.parse()?),
+                ("width", value) => width = Some(value.parse()?),
+                ("height", value) => height = Some(value.parse()?),
+                (key, _) => anyhow::bail!("unknown level header key `{}`", key),
+            }
+        }
+
+        let grid: Vec<String> = lines.map(|line| line.to_string()).collect();
+        if grid.is_empty() {
+            anyhow::bail!("level has no grid, is the `---` separator missing?");
+        }
+        let starts = grid
+            .iter()
+            .flat_map(|row| row.chars())
+            .filter(|&c| c == 'S' || c == 's')
+            .count();
+        if starts != 1 {
+            anyhow::bail!("level needs exactly one `S` start marker, found {}", starts);
+        }
+
+        for number in '1'..='9' {
+            let count = grid
+                .iter()
+                .flat_map(|row| row.chars())
+                .filter(|&c| c == number)
+                .count();
+            if count != 0 && count != 2 {
+                anyhow::bail!(
+                    "teleporter `{}` needs exactly two tiles, found {}",
+                    number,
+                    count
+                );
+            }
+        }
+
+        if runs == 0 {
+            anyhow::bail!("a level needs at least one run");
+        }
+
+        let longest_row = grid
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = width.unwrap_or(longest_row as u32);
+        let height = height.unwrap_or(grid.len() as u32);
+        if longest_row as u32 > width || grid.len() as u32 > height {
+            anyhow::bail!(
+                "grid is {}x{} but the level is declared as {}x{}",
+                longest_row,
+                grid.len(),
+                width,
+                height
+            );
+        }
+
+        Ok(Level {
+            name: name.unwrap_or_default(),
+            turns,
+            runs,
+            par: par.unwrap_or(turns * runs),
+            width,
+            height,
+            grid,
+        })
+    }
+}
+
+/// The level files in the order they are played and the file shown after
+/// the last one, relative to the manifest.
+///
+/// ```text
+/// level: level_0.level
+/// level: level_1.level
+/// won: you_won.level
+/// ```
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub levels: Vec<String>,
+    pub you_won: String,
+}
+
+impl Manifest {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let mut levels = vec![];
+        let mut you_won = None;
+
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match split_entry(line)? {
+                ("level", file) => levels.push(file.to_string()),
+                ("won", file) => you_won = Some(file.to_string()),
+                (key, _) => anyhow::bail!("unknown manifest key `{}`", key),
+            }
+        }
+
+        let you_won = you_won.ok_or_else(|| anyhow::anyhow!("manifest has no `won` entry"))?;
+        Ok(Manifest { levels, you_won })
+    }
+}
+
+fn split_entry(line: &str) -> anyhow::Result<(&str, &str)> {
+    let mut parts = line.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) => Ok((key.trim(), value.trim())),
+        _ => anyhow::bail!("expected `key: value`, found `{}`", line),
+    }
+}
@@ -0,0 +1,25 @@
+//! The rules of the game, without any rendering or input handling.
+//!
+//! A [`Board`] holds the tiles of a level and a [`Simulation`] moves the
+//! rocket across it one tick at a time, reporting what happened as
+//! [`Event`]s. The Bevy systems in `main.rs` translate input into
+//! [`Simulation::steer`] calls and events into entities. [`solve`] searches
+//! for a run that reaches a target within the turn budget.
+
+pub mod bindings;
+pub mod board;
+pub mod level;
+pub mod progress;
+pub mod save;
+pub mod score;
+pub mod simulation;
+pub mod solver;
+
+pub use bindings::{Action, Bindings};
+pub use board::{Board, Deflector, Direction, KeyColor, Position, Tile};
+pub use level::{Level, Manifest};
+pub use progress::Progress;
+pub use save::{BestResult, SaveData, Settings};
+pub use score::Score;
+pub use simulation::{Event, Simulation};
+pub use solver::{solve, solve_from, solve_runs, Move, Outlook, Planner};
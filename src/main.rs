@@ -1,33 +1,76 @@
+use std::collections::{HashSet, VecDeque};
+
 use bevy::core::FixedTimestep;
 use bevy::prelude::*;
 use bevy::render::pass::ClearColor;
 
+mod actions;
+mod assets;
+mod gamepad;
+mod mouse;
+mod screens;
+mod storage;
+mod touch;
+
+use actions::ActionMap;
+use assets::{LevelData, LevelLoader, LevelManifest, LevelManifestLoader};
+use butterfly_effect::{
+    Action, BestResult, Board, Deflector, Direction, Event, KeyColor, Move, Outlook, Planner,
+    Position, SaveData, Score, Simulation, Tile,
+};
+use screens::{AppState, ControlsSelection, LevelSelection};
+use touch::ControlButton;
+
 const SCORE_BOARD_HEIGHT: u32 = 2;
-const ARENA_HEIGHT: u32 = 16;
-const ARENA_WIDTH: u32 = 24;
+const DEFAULT_ARENA_HEIGHT: u32 = 16;
+const DEFAULT_ARENA_WIDTH: u32 = 24;
 
 const SPRITE_HEIGHT: u32 = 32;
 const SPRITE_WIDTH: u32 = 32;
 
-const WINDOW_HEIGHT: u32 = (ARENA_HEIGHT + SCORE_BOARD_HEIGHT) * SPRITE_HEIGHT;
-const WINDOW_WIDTH: u32 = ARENA_WIDTH * SPRITE_WIDTH;
+/// Direction keys pressed faster than the rocket moves are queued up to this many.
+const INPUT_BUFFER_SIZE: usize = 4;
 
-const MAX_TURNS: u32 = 10;
+/// Search states a search for the runs left in a level expands every frame,
+/// so it never holds up the game for long.
+const SEARCH_STEPS: u32 = 500;
+
+const WINDOW_HEIGHT: u32 = (DEFAULT_ARENA_HEIGHT + SCORE_BOARD_HEIGHT) * SPRITE_HEIGHT;
+const WINDOW_WIDTH: u32 = DEFAULT_ARENA_WIDTH * SPRITE_WIDTH;
 
 #[derive(SystemLabel, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum RocketMovement {
     Input,
+    /// Turns pointer presses and releases into swipes and taps.
+    Gesture,
     Movement,
     Reset,
     Target,
-    Loading,
     Path,
 }
 
-#[derive(Default, Copy, Clone, Eq, PartialEq, Hash)]
-struct Position {
-    x: i32,
-    y: i32,
+/// Size of the current level in tiles, including the border walls.
+struct Arena {
+    width: u32,
+    height: u32,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_ARENA_WIDTH,
+            height: DEFAULT_ARENA_HEIGHT,
+        }
+    }
+}
+
+impl Arena {
+    /// Tiles stay square and the arena plus scoreboard is fitted into the window.
+    fn tile_size(&self, window: &Window) -> f32 {
+        let tile_width = window.width() / self.width as f32;
+        let tile_height = window.height() / (self.height + SCORE_BOARD_HEIGHT) as f32;
+        tile_width.min(tile_height)
+    }
 }
 
 struct Size {
@@ -43,51 +86,168 @@ impl Size {
     }
 }
 
+/// The keys picked up during the current run, copied from the
+/// [`Simulation`] like the position of the rocket.
+#[derive(Default)]
 struct Rocket {
-    direction: Direction,
-    turns_left: u32,
+    keys: Vec<KeyColor>,
+}
+
+/// What occupies every tile of the arena. Kept in sync with the wall and
+/// target entities whenever they are spawned or despawned.
+struct Grid(Board);
+
+impl Default for Grid {
+    fn default() -> Self {
+        Grid(Board::new(0, 0, Position::default()))
+    }
+}
+
+/// Directions pressed since the last movement tick, oldest first. Every tick
+/// takes one of them.
+#[derive(Default)]
+struct InputBuffer(VecDeque<Direction>);
+
+impl InputBuffer {
+    /// Presses beyond [`INPUT_BUFFER_SIZE`] are dropped.
+    fn push(&mut self, dir: Direction) {
+        if self.0.len() < INPUT_BUFFER_SIZE {
+            self.0.push_back(dir);
+        }
+    }
+}
+
+/// The walls left behind during the current run, and the cracked walls it
+/// hit. The hits are only filled in once the run is complete.
+#[derive(Default)]
+struct RocketPath {
+    walls: Vec<(Entity, Position)>,
+    hits: Vec<Position>,
 }
 
+/// Every completed run of the current level, oldest first.
+#[derive(Default)]
+struct RunHistory(Vec<RocketPath>);
+
+/// The search started after the last run ended, whose result decides
+/// whether the level can still be cleared. A newer search replaces it.
 #[derive(Default)]
-struct RocketPath(Vec<Position>, Vec<Entity>);
+struct PathFinder(Option<Planner>);
+
+/// The search started by the last hint request, with the turns the run had
+/// left at the time. A newer request replaces it.
+#[derive(Default)]
+struct HintSearch(Option<(Planner, u32)>);
+
+/// Scoreboard position relative to the arena: `column` is a fraction of the
+/// arena width, `offset` is added in tiles and `row` counts up from the top
+/// border.
+struct HudPosition {
+    column: (i32, i32),
+    offset: i32,
+    row: i32,
+}
+
+enum HudText {
+    TurnsLeft,
+    Runs,
+    Hints,
+    /// The key bound to an action, shown in place of a control sprite that
+    /// pictures the given key while that key is not bound.
+    Binding(Action, KeyCode),
+}
+
+/// The key pictured on a control sprite.
+struct ControlPicture(KeyCode);
+
+/// Marks where the next turn of the solution is. Removed once the rocket
+/// turns, since the hint was for the turns it had left.
+struct Hint {
+    turns_left: u32,
+}
+struct HintEvent();
 
 struct Wall {}
 struct Target {}
+
+/// One end of a teleporter pair. Its sprite is added by
+/// [`teleporter_sprites`].
+struct Teleporter {
+    number: u8,
+}
+
+/// A one-way gate. Its sprite is added by [`gate_sprites`].
+struct Gate {
+    direction: Direction,
+}
+
+struct DeflectorSprite {}
+
+/// A key or door of the given colour.
+struct Keyed {
+    color: KeyColor,
+}
+
+fn key_color(color: KeyColor) -> Color {
+    match color {
+        KeyColor::Red => Color::rgb(1.0, 0.25, 0.25),
+        KeyColor::Green => Color::rgb(0.3, 0.85, 0.35),
+        KeyColor::Blue => Color::rgb(0.3, 0.5, 1.0),
+        KeyColor::Yellow => Color::rgb(1.0, 0.9, 0.2),
+    }
+}
+
+/// A cracked wall on the arena, with the hits it takes before crumbling.
+struct CrackedWall {
+    hits_left: u8,
+}
+
+/// Teleporter pairs are told apart by their tint, so every third pair looks
+/// the same.
+const TELEPORTER_TINTS: [Color; 3] = [
+    Color::rgb(1.0, 1.0, 1.0),
+    Color::rgb(1.0, 0.2, 0.2),
+    Color::rgb(0.2, 0.4, 1.0),
+];
 struct TargetEvent();
 struct FindPathEvent();
 
 struct ResetEvent();
-
-struct NextLevelEvent();
-struct GameOverEvent();
+struct UndoEvent();
 
 #[derive(Default)]
 struct LevelInfo {
     current_level: usize,
+    level_count: usize,
     counter_completion: u32,
+    hints_used: u32,
+    turns_used: u32,
+    runs_used: u32,
+    loaded: bool,
+    required_completions: u32,
+    par: u32,
+    /// Shown instead of the hint count when a hint request found no turn to
+    /// take, until the run ends.
+    hint_note: Option<&'static str>,
+    /// Set once the level is cleared.
+    score: Option<Score>,
 }
 
-#[derive(PartialEq, Copy, Clone)]
-enum Direction {
-    Left,
-    Up,
-    Right,
-    Down,
-    StandStill,
-}
-
-impl Direction {
-    fn opposite(self) -> Self {
-        match self {
-            Self::Left => Self::Right,
-            Self::Right => Self::Left,
-            Self::Up => Self::Down,
-            Self::Down => Self::Up,
-            Self::StandStill => Self::StandStill,
+impl LevelInfo {
+    /// Counts the turns of a run that ended, whether it reached a target, was
+    /// reset or undone. Runs that never left the start are not counted.
+    fn end_run(&mut self, simulation: &Simulation) {
+        if simulation.path().len() > 1 || simulation.turns_left() < simulation.max_turns() {
+            self.turns_used += simulation.max_turns() - simulation.turns_left();
+            self.runs_used += 1;
         }
     }
 }
 
+struct LevelAssets {
+    manifest: Handle<LevelManifest>,
+}
+
 fn spawn_wall(
     commands: &mut Commands,
     materials: &mut ResMut<Assets<ColorMaterial>>,
@@ -143,235 +303,299 @@ fn spawn_target(
         .insert(Size::square(0.9));
 }
 
-fn spawn_border(
-    commands: &mut Commands,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-    asset_server: &Res<AssetServer>,
+fn spawn_teleporter(commands: &mut Commands, position: Position, number: u8) {
+    commands
+        .spawn()
+        .insert(Teleporter { number })
+        .insert(position)
+        // The ring only fills the middle of its frame.
+        .insert(Size::square(2.5));
+}
+
+/// Gives new teleporters the ring from the effects sheet.
+fn teleporter_sprites(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    query: Query<(Entity, &Teleporter), Added<Teleporter>>,
 ) {
-    for y in 0..ARENA_HEIGHT as i32 {
-        spawn_wall(commands, materials, asset_server, Position { x: 0, y });
-        spawn_wall(
-            commands,
-            materials,
-            asset_server,
-            Position {
-                x: ARENA_WIDTH as i32 - 1,
-                y,
-            },
-        );
-    }
-    for x in 1..ARENA_WIDTH as i32 - 1 {
-        spawn_wall(commands, materials, asset_server, Position { x, y: 0 });
-        spawn_wall(
-            commands,
-            materials,
-            asset_server,
-            Position {
-                x,
-                y: ARENA_HEIGHT as i32 - 1,
+    for (entity, teleporter) in query.iter() {
+        let texture_handle = asset_server.load("LunarLander/Effects/Fx_04.png");
+        let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(64.0, 64.0), 4, 1);
+        let tint = TELEPORTER_TINTS[(teleporter.number as usize - 1) % TELEPORTER_TINTS.len()];
+        commands.entity(entity).insert_bundle(SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(texture_atlas),
+            sprite: TextureAtlasSprite {
+                index: 0,
+                color: tint,
+                ..Default::default()
             },
-        );
+            ..Default::default()
+        });
     }
 }
 
-fn load_game_over(
+fn spawn_gate(commands: &mut Commands, position: Position, direction: Direction) {
+    commands
+        .spawn()
+        .insert(Gate { direction })
+        .insert(position)
+        .insert(Size::square(0.9));
+}
+
+/// Gives new gates an arrow from the effects sheet, pointing the way the
+/// rocket may pass.
+fn gate_sprites(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
-    wall_query: Query<Entity, With<Wall>>,
-    target_query: Query<Entity, With<Target>>,
-    mut reader: EventReader<GameOverEvent>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    query: Query<(Entity, &Gate), Added<Gate>>,
 ) {
-    if reader.iter().next().is_some() {
-        // unload all walls
-        for wall in wall_query.iter() {
-            commands.entity(wall).despawn();
-        }
-
-        for target in target_query.iter() {
-            commands.entity(target).despawn();
-        }
-
-        let game_over_data = vec![
-            "                     ".to_string(),
-            "  WWW  WWW W   W WWW ".to_string(),
-            "  W    W W WW WW W   ".to_string(),
-            "  W WW WWW W W W WWW ".to_string(),
-            "  W  W W W W   W W   ".to_string(),
-            "  WWWW W W W   W WWW ".to_string(),
-            "                     ".to_string(),
-            "   WWW W  W WWW WWW  ".to_string(),
-            "   W W W  W W   W W  ".to_string(),
-            "   W W W  W WWW WWW  ".to_string(),
-            "   W W W  W W   WW   ".to_string(),
-            "   WWW  WW  WWW W W  ".to_string(),
-            "                     ".to_string(),
-            "                     ".to_string(),
-        ];
-
-        load_level_from_data(
-            &mut commands,
-            &mut materials,
-            &asset_server,
-            &game_over_data,
-        );
+    for (entity, gate) in query.iter() {
+        let texture_handle = asset_server.load("LunarLander/Effects/Fx_03.png");
+        let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(16.0, 16.0), 2, 1);
+        // The arrow points left.
+        let angle = match gate.direction {
+            Direction::Up => -std::f32::consts::PI * 0.5,
+            Direction::Right => std::f32::consts::PI,
+            Direction::Down => std::f32::consts::PI * 0.5,
+            _ => 0.0,
+        };
+        commands.entity(entity).insert_bundle(SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(texture_atlas),
+            transform: Transform::from_rotation(Quat::from_rotation_z(angle)),
+            ..Default::default()
+        });
     }
 }
 
-fn load_level(
+fn spawn_deflector(
     commands: &mut Commands,
     materials: &mut ResMut<Assets<ColorMaterial>>,
-    asset_server: &Res<AssetServer>,
-    current_level: usize,
+    position: Position,
+    deflector: Deflector,
 ) {
-    let level_data_0: Vec<String> = vec![
-        "WWWWWWWWWWW WWWWWWWWWT".to_string(),
-        "WWWWWWWWWW   WWWWWWWW ".to_string(),
-        "WWWWWWWWW             ".to_string(),
-        "WWWWWWWWWW   WWWWWWWW ".to_string(),
-        "WWWWWWWWWWW WWWWWWWWW ".to_string(),
-        "WWWWWWWWWWW WWWWWWWW  ".to_string(),
-        "                      ".to_string(),
-        "  WWWWWWWWW WWWWWWWWWW".to_string(),
-        " WWWWWWWWWW WWWWWWWWWW".to_string(),
-        " WWWWWWWWW  WWWWWWWWWW".to_string(),
-        "            WWWWWWWWWW".to_string(),
-        " WWWWWWWWW  WWWWWWWWWW".to_string(),
-        " WWWWWWWWWW WWWWWWWWWW".to_string(),
-        "SWWWWWWWWWWWWWWWWWWWWW".to_string(),
-    ];
-    let level_data_1: Vec<String> = vec![
-        "          WWWW       T".to_string(),
-        "   WWWW   WWWW        ".to_string(),
-        "   WWWW   WWWW        ".to_string(),
-        "   WWWW   WWWW        ".to_string(),
-        "   WWWW   WWWW        ".to_string(),
-        "   WWWW   WWWW   WWWWW".to_string(),
-        "   WWWW   WWWW   WWWWW".to_string(),
-        "   WWWW   WWWW   WWWWW".to_string(),
-        "   WWWW   WWWW   WWWWW".to_string(),
-        "   WWWW   WWWW   WWWWW".to_string(),
-        "   WWWW          WWWWW".to_string(),
-        "   WWWW          WWWWW".to_string(),
-        "   WWWW          WWWWW".to_string(),
-        "s WWWWW          WWWWW".to_string(),
-    ];
-
-    let level_data_2: Vec<String> = vec![
-        "                      ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWW W WWWWWWWW ".to_string(),
-        "                      ".to_string(),
-        " WWWWWWWWW T WWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        " WWWWWWWWWW WWWWWWWWW ".to_string(),
-        "S                     ".to_string(),
-    ];
+    let angle = match deflector {
+        Deflector::Slash => std::f32::consts::PI * 0.25,
+        Deflector::Backslash => -std::f32::consts::PI * 0.25,
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(Color::rgb(0.6, 0.9, 1.0).into()),
+            sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
+            transform: Transform::from_rotation(Quat::from_rotation_z(angle)),
+            ..Default::default()
+        })
+        .insert(DeflectorSprite {})
+        .insert(position)
+        .insert(Size {
+            width: 1.2,
+            height: 0.15,
+        });
+}
 
-    let level_data_3: Vec<String> = vec![
-        "          WW          ".to_string(),
-        "          WW          ".to_string(),
-        "         WTTW         ".to_string(),
-        "         W  W         ".to_string(),
-        "W    W   W  W   W    W".to_string(),
-        " TW  W  W    W  W  WT ".to_string(),
-        " W W W W  WW  W W W W ".to_string(),
-        " W  WW W W  W W WW  W ".to_string(),
-        "  W  WW  W  W  WW  W  ".to_string(),
-        "   W W    WW    W W   ".to_string(),
-        "    WW          WW    ".to_string(),
-        "     WWWWW  WWWWW     ".to_string(),
-        "        WW  WW        ".to_string(),
-        "S                     ".to_string(),
-    ];
+fn spawn_key(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    position: Position,
+    color: KeyColor,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(key_color(color).into()),
+            sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
+            ..Default::default()
+        })
+        .insert(Keyed { color })
+        .insert(position)
+        .insert(Size::square(0.4));
+}
 
-    let you_won = vec![
-        "                     T".to_string(),
-        "     W W  WWW  W W    ".to_string(),
-        "     W W  W W  W W    ".to_string(),
-        "     WWW  W W  W W    ".to_string(),
-        "      W   W W  W W    ".to_string(),
-        "      W   WWW  WWW    ".to_string(),
-        "                      ".to_string(),
-        "                      ".to_string(),
-        "    W   W WWW W   W   ".to_string(),
-        "    W   W W W WW  W   ".to_string(),
-        "    W   W W W W W W   ".to_string(),
-        "    W W W W W W  WW   ".to_string(),
-        "     W W  WWW W   W   ".to_string(),
-        "                      ".to_string(),
-    ];
+/// Doors are walls in the colour of their key.
+fn spawn_door(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    asset_server: &Res<AssetServer>,
+    position: Position,
+    color: KeyColor,
+) {
+    let texture_handle = asset_server.load("LunarLander/Moon Tiles/MoonTile_square.png");
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(ColorMaterial::modulated_texture(
+                texture_handle,
+                key_color(color),
+            )),
+            sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
+            ..Default::default()
+        })
+        .insert(Keyed { color })
+        .insert(position)
+        .insert(Size::square(0.9));
+}
 
-    let levels = vec![&level_data_0, &level_data_1, &level_data_2, &level_data_3];
+/// Cracked walls look more damaged with every hit.
+fn crack(hits_left: u8) -> (Color, Size) {
+    match hits_left {
+        1 => (Color::rgb(1.0, 0.35, 0.25), Size::square(0.7)),
+        2 => (Color::rgb(1.0, 0.7, 0.5), Size::square(0.8)),
+        _ => (Color::rgb(0.8, 0.8, 0.8), Size::square(0.9)),
+    }
+}
 
-    let current_level_data = levels.get(current_level);
-    let level_data: &Vec<String>;
+/// Keeps the cracked walls in line with the grid: hit walls look more
+/// damaged, broken ones are despawned and the ones repaired by a reset or
+/// undo are spawned again.
+fn cracked_walls(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    grid: Res<Grid>,
+    mut query: Query<(
+        Entity,
+        &Position,
+        &mut CrackedWall,
+        &mut Size,
+        &Handle<ColorMaterial>,
+    )>,
+) {
+    if !grid.is_changed() {
+        return;
+    }
+    let mut spawned = HashSet::new();
+    for (entity, &pos, mut cracked_wall, mut size, material) in query.iter_mut() {
+        match grid.0.tile(pos) {
+            Tile::Cracked(hits_left) => {
+                if hits_left != cracked_wall.hits_left {
+                    cracked_wall.hits_left = hits_left;
+                    let (color, crack_size) = crack(hits_left);
+                    *size = crack_size;
+                    if let Some(material) = materials.get_mut(material) {
+                        material.color = color;
+                    }
+                }
+                spawned.insert(pos);
+            }
+            _ => commands.entity(entity).despawn(),
+        }
+    }
 
-    if let Some(data) = current_level_data {
-        level_data = data;
-    } else {
-        level_data = &you_won;
+    for (pos, tile) in grid.0.tiles() {
+        if let Tile::Cracked(hits_left) = tile {
+            if spawned.contains(&pos) {
+                continue;
+            }
+            let (color, size) = crack(hits_left);
+            let texture_handle = asset_server.load("LunarLander/Moon Tiles/MoonTile_square.png");
+            commands
+                .spawn_bundle(SpriteBundle {
+                    material: materials
+                        .add(ColorMaterial::modulated_texture(texture_handle, color)),
+                    sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
+                    ..Default::default()
+                })
+                .insert(CrackedWall { hits_left })
+                .insert(pos)
+                .insert(size);
+        }
     }
+}
 
-    load_level_from_data(commands, materials, asset_server, level_data);
+fn spawn_hint(
+    commands: &mut Commands,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    hint: Move,
+    turns_left: u32,
+) {
+    let color = Color::rgb(0.3, 0.8, 1.0);
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(color.into()),
+            sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
+            ..Default::default()
+        })
+        .insert(Hint { turns_left })
+        .insert(hint.position)
+        .insert(Size::square(0.5));
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.add(color.into()),
+            sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
+            ..Default::default()
+        })
+        .insert(Hint { turns_left })
+        .insert(hint.position.step(hint.direction))
+        .insert(Size::square(0.25));
 }
 
-fn load_level_from_data(
+fn spawn_board(
     commands: &mut Commands,
     materials: &mut ResMut<Assets<ColorMaterial>>,
     asset_server: &Res<AssetServer>,
-    level_data: &Vec<String>,
+    board: &Board,
 ) {
-    for (y, line_data) in level_data.iter().rev().enumerate() {
-        for (x, c) in line_data.chars().enumerate() {
-            let pos = Position {
-                x: x as i32 + 1,
-                y: y as i32 + 1,
-            };
-            if c == 'W' {
+    for (pos, tile) in board.tiles() {
+        match tile {
+            Tile::Wall => {
                 spawn_wall(commands, materials, asset_server, pos);
-            } else if c == 'T' {
-                spawn_target(commands, materials, pos);
             }
+            Tile::Target => spawn_target(commands, materials, pos),
+            Tile::Teleporter(number) => spawn_teleporter(commands, pos, number),
+            Tile::Gate(direction) => spawn_gate(commands, pos, direction),
+            Tile::Deflector(deflector) => spawn_deflector(commands, materials, pos, deflector),
+            Tile::Key(color) => spawn_key(commands, materials, pos, color),
+            Tile::Door(color) => spawn_door(commands, materials, asset_server, pos, color),
+            // Spawned by `cracked_walls` once the grid is set.
+            Tile::Cracked(_) => {}
+            Tile::Empty => {}
         }
     }
 }
 
-fn load_next_level(
+fn spawn_level(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
-    wall_query: Query<Entity, With<Wall>>,
-    target_query: Query<Entity, With<Target>>,
+    level_assets: Res<LevelAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    levels: Res<Assets<LevelData>>,
     mut level_info: ResMut<LevelInfo>,
-    mut reader: EventReader<NextLevelEvent>,
+    mut arena: ResMut<Arena>,
+    mut simulation: ResMut<Simulation>,
+    mut grid: ResMut<Grid>,
+    mut rocket_path: ResMut<RocketPath>,
+    mut history: ResMut<RunHistory>,
 ) {
-    if reader.iter().next().is_some() {
-        // unload all walls
-        for wall in wall_query.iter() {
-            commands.entity(wall).despawn();
-        }
-
-        for target in target_query.iter() {
-            commands.entity(target).despawn();
-        }
-
-        level_info.current_level += 1;
-        level_info.counter_completion = 0;
-
-        spawn_border(&mut commands, &mut materials, &asset_server);
-        load_level(
-            &mut commands,
-            &mut materials,
-            &asset_server,
-            level_info.current_level,
-        );
+    if level_info.loaded {
+        return;
+    }
+    let manifest = match manifests.get(&level_assets.manifest) {
+        Some(manifest) => manifest,
+        None => return,
+    };
+
+    level_info.level_count = manifest.levels.len();
+    let level_handle = match manifest.levels.get(level_info.current_level) {
+        Some(level_handle) => level_handle,
+        None => return,
+    };
+
+    if let Some(level_data) = levels.get(level_handle) {
+        let level = &level_data.level;
+        info!("loading level {}: {}", level_info.current_level, level.name);
+        let board = Board::from_level(level);
+        *arena = Arena {
+            width: board.width(),
+            height: board.height(),
+        };
+        spawn_board(&mut commands, &mut materials, &asset_server, &board);
+        *simulation = Simulation::new(board.start(), level.turns);
+        grid.0 = board;
+        *rocket_path = RocketPath::default();
+        history.0.clear();
+        level_info.required_completions = level.runs;
+        level_info.par = level.par;
+        level_info.loaded = true;
     }
 }
 
@@ -392,55 +616,75 @@ fn setup_scoreboard(
             sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
             ..Default::default()
         })
-        .insert(Position {
-            x: ARENA_WIDTH as i32 / 4 * 3,
-            y: ARENA_HEIGHT as i32 + 1,
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (3, 4),
+            offset: 0,
+            row: 1,
         })
-        .insert(Size::square(0.8));
+        .insert(Size::square(0.8))
+        .insert(ControlButton(Action::Up))
+        .insert(ControlPicture(KeyCode::W));
     commands
         .spawn_bundle(SpriteBundle {
             material: materials.add(control_a.into()),
             sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
             ..Default::default()
         })
-        .insert(Position {
-            x: ARENA_WIDTH as i32 / 4 * 3 - 1,
-            y: ARENA_HEIGHT as i32,
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (3, 4),
+            offset: -1,
+            row: 0,
         })
-        .insert(Size::square(0.8));
+        .insert(Size::square(0.8))
+        .insert(ControlButton(Action::Left))
+        .insert(ControlPicture(KeyCode::A));
     commands
         .spawn_bundle(SpriteBundle {
             material: materials.add(control_s.into()),
             sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
             ..Default::default()
         })
-        .insert(Position {
-            x: ARENA_WIDTH as i32 / 4 * 3,
-            y: ARENA_HEIGHT as i32,
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (3, 4),
+            offset: 0,
+            row: 0,
         })
-        .insert(Size::square(0.8));
+        .insert(Size::square(0.8))
+        .insert(ControlButton(Action::Down))
+        .insert(ControlPicture(KeyCode::S));
     commands
         .spawn_bundle(SpriteBundle {
             material: materials.add(control_d.into()),
             sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
             ..Default::default()
         })
-        .insert(Position {
-            x: ARENA_WIDTH as i32 / 4 * 3 + 1,
-            y: ARENA_HEIGHT as i32,
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (3, 4),
+            offset: 1,
+            row: 0,
         })
-        .insert(Size::square(0.8));
+        .insert(Size::square(0.8))
+        .insert(ControlButton(Action::Right))
+        .insert(ControlPicture(KeyCode::D));
     commands
         .spawn_bundle(SpriteBundle {
             material: materials.add(control_r.into()),
             sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
             ..Default::default()
         })
-        .insert(Position {
-            x: ARENA_WIDTH as i32 / 4 * 3 + 2,
-            y: ARENA_HEIGHT as i32 + 1,
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (3, 4),
+            offset: 2,
+            row: 1,
         })
-        .insert(Size::square(0.8));
+        .insert(Size::square(0.8))
+        .insert(ControlButton(Action::Reset))
+        .insert(ControlPicture(KeyCode::R));
 
     commands
         .spawn_bundle(Text2dBundle {
@@ -457,22 +701,104 @@ fn setup_scoreboard(
             },
             ..Default::default()
         })
-        .insert(Position {
-            x: ARENA_WIDTH as i32 / 3 + 1,
-            y: ARENA_HEIGHT as i32,
-        });
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (1, 3),
+            offset: 1,
+            row: 0,
+        })
+        .insert(HudText::TurnsLeft);
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: vec![TextSection {
+                    value: "runs: ".to_string(),
+                    style: TextStyle {
+                        font: asset_server.load("fonts/press-start/prstart.ttf"),
+                        font_size: 20.0,
+                        color: Color::rgb(0.125, 0.164, 0.266),
+                    },
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (1, 3),
+            offset: 1,
+            row: 1,
+        })
+        .insert(HudText::Runs);
+
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: vec![TextSection {
+                    value: "hints: ".to_string(),
+                    style: TextStyle {
+                        font: asset_server.load("fonts/press-start/prstart.ttf"),
+                        font_size: 20.0,
+                        color: Color::rgb(0.125, 0.164, 0.266),
+                    },
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (0, 1),
+            offset: 3,
+            row: 1,
+        })
+        .insert(HudText::Hints);
+
+    let bindings = [
+        (Action::Up, KeyCode::W, 0, 1),
+        (Action::Left, KeyCode::A, -1, 0),
+        (Action::Down, KeyCode::S, 0, 0),
+        (Action::Right, KeyCode::D, 1, 0),
+        (Action::Reset, KeyCode::R, 2, 1),
+    ];
+    for &(action, pictured, offset, row) in bindings.iter() {
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    String::new(),
+                    TextStyle {
+                        font: asset_server.load("fonts/press-start/prstart.ttf"),
+                        font_size: 12.0,
+                        color: Color::rgb(0.125, 0.164, 0.266),
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Center,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                ..Default::default()
+            })
+            .insert(Position::default())
+            .insert(HudPosition {
+                column: (3, 4),
+                offset,
+                row,
+            })
+            .insert(HudText::Binding(action, pictured));
+    }
 }
 
 fn _setup_statusbar(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-){
+) {
     let texture_handle = asset_server.load("status_bar.png");
-        let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 8.0), 4, 1);
-        let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 8.0), 4, 1);
+    let texture_atlas_handle = texture_atlases.add(texture_atlas);
 
-        commands
+    commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: texture_atlas_handle,
             sprite: TextureAtlasSprite {
@@ -480,166 +806,144 @@ fn _setup_statusbar(
                 ..Default::default()
             },
             ..Default::default()
-        }).insert(Position {
-            x: ARENA_WIDTH as i32 / 2,
-            y: ARENA_HEIGHT as i32 + 1 ,
+        })
+        .insert(Position::default())
+        .insert(HudPosition {
+            column: (1, 2),
+            offset: 0,
+            row: 1,
         });
 }
 
 fn setup(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
     mut level_info: ResMut<LevelInfo>,
 ) {
     commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.insert_resource(LevelAssets {
+        manifest: asset_server.load("levels/levels.manifest"),
+    });
 
-    // Add border walls
     level_info.current_level = 0;
     level_info.counter_completion = 0;
-    spawn_border(&mut commands, &mut materials, &asset_server);
-    load_level(
-        &mut commands,
-        &mut materials,
-        &asset_server,
-        level_info.current_level,
-    );
+    level_info.loaded = false;
 }
 
-fn spawn_rocket(
+fn start_level(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut rocket_path: ResMut<RocketPath>,
+    simulation: Res<Simulation>,
+    mut level_info: ResMut<LevelInfo>,
+    mut hint_search: ResMut<HintSearch>,
 ) {
+    *hint_search = HintSearch::default();
+    level_info.counter_completion = 0;
+    level_info.hints_used = 0;
+    level_info.hint_note = None;
+    level_info.turns_used = 0;
+    level_info.runs_used = 0;
+    level_info.score = None;
+    level_info.loaded = false;
+
     let texture_handle = asset_server.load("LunarLander/Ships/Spaceships_green_4.png");
-    let start_position = Position { x: 1, y: 1 };
     commands
         .spawn_bundle(SpriteBundle {
             material: materials.add(texture_handle.into()),
             sprite: Sprite::new(Vec2::new(SPRITE_WIDTH as f32, SPRITE_HEIGHT as f32)),
             ..Default::default()
         })
-        .insert(Rocket {
-            direction: Direction::StandStill,
-            turns_left: MAX_TURNS,
-        })
-        .insert(start_position.clone())
-        .insert(Size::square(0.8))
-        .id();
-    rocket_path.0 = vec![start_position.clone()];
-    rocket_path.1 = vec![];
+        .insert(Rocket::default())
+        .insert(simulation.position())
+        .insert(Size::square(0.8));
 }
 
-fn rocket_movement_input(keyboard_input: Res<Input<KeyCode>>, mut rockets: Query<&mut Rocket>) {
-    let right = keyboard_input.pressed(KeyCode::Right) || keyboard_input.pressed(KeyCode::D);
-    let left = keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::A);
-    let up = keyboard_input.pressed(KeyCode::Up) || keyboard_input.pressed(KeyCode::W);
-    let down = keyboard_input.pressed(KeyCode::Down) || keyboard_input.pressed(KeyCode::S);
-
-    if let Some(mut rocket) = rockets.iter_mut().next() {
-        if rocket.turns_left <= 0 {
-            return;
-        }
-        let old_dir = rocket.direction.clone();
-        let dir: Direction = if left {
-            Direction::Left
-        } else if down {
-            Direction::Down
-        } else if up {
-            Direction::Up
-        } else if right {
-            Direction::Right
-        } else {
-            rocket.direction
-        };
-        if dir != rocket.direction.opposite() {
-            rocket.direction = dir;
-        }
+/// The entities making up a level on the arena.
+type LevelEntity = Or<(
+    With<Wall>,
+    With<Target>,
+    With<Teleporter>,
+    With<Gate>,
+    With<DeflectorSprite>,
+    With<Keyed>,
+    With<CrackedWall>,
+    With<Hint>,
+    With<Rocket>,
+)>;
+
+/// Despawns everything on the arena when leaving a screen that shows a level.
+fn despawn_level(
+    mut commands: Commands,
+    query: Query<Entity, LevelEntity>,
+    mut grid: ResMut<Grid>,
+    mut rocket_path: ResMut<RocketPath>,
+    mut history: ResMut<RunHistory>,
+    mut input_buffer: ResMut<InputBuffer>,
+    mut path_finder: ResMut<PathFinder>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    *grid = Grid::default();
+    *rocket_path = RocketPath::default();
+    history.0.clear();
+    input_buffer.0.clear();
+    *path_finder = PathFinder::default();
+}
 
-        if rocket.direction != old_dir {
-            rocket.turns_left -= 1;
+fn rocket_movement_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    action_map: Res<ActionMap>,
+    level_info: Res<LevelInfo>,
+    mut input_buffer: ResMut<InputBuffer>,
+) {
+    if !level_info.loaded {
+        return;
+    }
+    for &action in Action::ALL.iter() {
+        if let Some(dir) = action.direction() {
+            if action_map.just_pressed(&keyboard_input, action) {
+                input_buffer.push(dir);
+            }
         }
     }
 }
+
 fn rocket_movement(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut rocket_query: Query<(&Rocket, &mut Position)>,
-    collider_query: Query<&Transform, With<Wall>>,
-    target_query: Query<&Transform, With<Target>>,
-    windows: Res<Windows>,
+    asset_server: Res<AssetServer>,
+    mut grid: ResMut<Grid>,
+    mut simulation: ResMut<Simulation>,
     mut target_writer: EventWriter<TargetEvent>,
     mut rocket_path: ResMut<RocketPath>,
-    asset_server: Res<AssetServer>,
+    mut history: ResMut<RunHistory>,
     mut level_info: ResMut<LevelInfo>,
+    mut input_buffer: ResMut<InputBuffer>,
+    state: Res<State<AppState>>,
 ) {
-    let window = windows.get_primary().unwrap();
-    if let Some((rocket, mut rocket_pos)) = rocket_query.iter_mut().next() {
-        let mut next_position = rocket_pos.clone();
-
-        match &rocket.direction {
-            Direction::Left => {
-                if rocket_pos.x > 0 {
-                    next_position.x -= 1;
-                }
-            }
-            Direction::Right => {
-                if rocket_pos.x < ARENA_WIDTH as i32 - 1 {
-                    next_position.x += 1;
-                }
-            }
-            Direction::Up => {
-                if rocket_pos.y < ARENA_HEIGHT as i32 - 1 {
-                    next_position.y += 1;
-                }
-            }
-            Direction::Down => {
-                if rocket_pos.y > 0 {
-                    next_position.y -= 1;
-                }
-            }
-            Direction::StandStill => {}
-        };
-
-        let mut no_collision = true;
-
-        for wall_transform in collider_query.iter() {
-            let next_transform_x = convert_x(next_position.x, window.width());
-            let next_transform_y = convert_y(next_position.y, window.height());
-            if wall_transform.translation.x == next_transform_x
-                && wall_transform.translation.y == next_transform_y
-            {
-                no_collision = false;
+    // The fixed timestep can not be combined with the state run criteria.
+    if *state.current() != AppState::Playing || !level_info.loaded {
+        return;
+    }
+    // A turn is only charged once the rocket moves on in the new direction.
+    if let Some(dir) = input_buffer.0.pop_front() {
+        simulation.steer(dir);
+    }
+    for event in simulation.step(&mut grid.0) {
+        match event {
+            // Cracked walls follow the grid, see `cracked_walls`.
+            Event::Moved(_) | Event::Teleported(_) | Event::WallHit(_) | Event::KeyCollected(_) => {
             }
-        }
-        if no_collision && rocket.direction != Direction::StandStill {
-            rocket_pos.x = next_position.x;
-            rocket_pos.y = next_position.y;
-            rocket_path.0.push(rocket_pos.clone());
-
-            if rocket_path.0.len() >= 3 {
-                let previous = rocket_path.0[rocket_path.0.len() - 3];
-                let middle = rocket_path.0[rocket_path.0.len() - 2];
-                let next = rocket_path.0[rocket_path.0.len() - 1];
-
-                if previous.x != next.x && previous.y != next.y {
-                    rocket_path.1.push(spawn_wall(
-                        &mut commands,
-                        &mut materials,
-                        &asset_server,
-                        middle,
-                    ));
-                }
+            Event::CornerWall(pos) => {
+                let wall = spawn_wall(&mut commands, &mut materials, &asset_server, pos);
+                rocket_path.walls.push((wall, pos));
             }
-        }
-
-        for target_transform in target_query.iter() {
-            let next_transform_x = convert_x(next_position.x, window.width());
-            let next_transform_y = convert_y(next_position.y, window.height());
-            if target_transform.translation.x == next_transform_x
-                && target_transform.translation.y == next_transform_y
-            {
+            Event::ReachedTarget(_) => {
+                level_info.end_run(&simulation);
+                rocket_path.hits = simulation.hits().to_vec();
+                history.0.push(std::mem::take(&mut *rocket_path));
                 level_info.counter_completion += 1;
                 target_writer.send(TargetEvent {});
             }
@@ -647,48 +951,151 @@ fn rocket_movement(
     }
 }
 
-fn size_scaling(windows: Res<Windows>, mut q: Query<(&Size, &mut Sprite)>) {
+fn size_scaling(windows: Res<Windows>, arena: Res<Arena>, mut q: Query<(&Size, &mut Sprite)>) {
     let window = windows.get_primary().unwrap();
+    let tile_size = arena.tile_size(window);
     for (sprite_size, mut sprite) in q.iter_mut() {
         sprite.size = Vec2::new(
-            sprite_size.width / ARENA_WIDTH as f32 * window.width() as f32,
-            sprite_size.height / (ARENA_HEIGHT + SCORE_BOARD_HEIGHT) as f32
-                * window.height() as f32,
+            sprite_size.width * tile_size,
+            sprite_size.height * tile_size,
         );
     }
 }
 
-fn convert(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
-    let tile_size = bound_window / bound_game;
-    pos / bound_game * bound_window - (bound_window / 2.) + (tile_size / 2.)
+/// Sprites from a texture atlas are scaled instead, relative to the size of
+/// their frame.
+fn atlas_scaling(
+    windows: Res<Windows>,
+    arena: Res<Arena>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut q: Query<(
+        &Size,
+        &Handle<TextureAtlas>,
+        &TextureAtlasSprite,
+        &mut Transform,
+    )>,
+) {
+    let window = windows.get_primary().unwrap();
+    let tile_size = arena.tile_size(window);
+    for (sprite_size, atlas_handle, sprite, mut transform) in q.iter_mut() {
+        if let Some(frame) = texture_atlases
+            .get(atlas_handle)
+            .and_then(|atlas| atlas.textures.get(sprite.index as usize))
+        {
+            transform.scale = Vec3::new(
+                sprite_size.width * tile_size / frame.width(),
+                sprite_size.height * tile_size / frame.height(),
+                1.0,
+            );
+        }
+    }
+}
+
+fn convert(pos: f32, bound_game: f32, tile_size: f32) -> f32 {
+    (pos - bound_game / 2.) * tile_size + (tile_size / 2.)
 }
 
-fn convert_x(pos: i32, window_width: f32) -> f32 {
-    convert(pos as f32, window_width, ARENA_WIDTH as f32)
+fn convert_x(pos: i32, tile_size: f32, arena: &Arena) -> f32 {
+    convert(pos as f32, arena.width as f32, tile_size)
 }
 
-fn convert_y(pos: i32, window_height: f32) -> f32 {
+fn convert_y(pos: i32, tile_size: f32, arena: &Arena) -> f32 {
     convert(
         pos as f32,
-        window_height,
-        (ARENA_HEIGHT + SCORE_BOARD_HEIGHT) as f32,
+        (arena.height + SCORE_BOARD_HEIGHT) as f32,
+        tile_size,
     )
 }
 
-fn position_translation(windows: Res<Windows>, mut q: Query<(&Position, &mut Transform)>) {
+/// The tile a coordinate returned by [`convert`] lies on.
+fn unconvert(coord: f32, bound_game: f32, tile_size: f32) -> i32 {
+    (coord / tile_size + bound_game / 2.).floor() as i32
+}
+
+/// The tile at a point in world coordinates, the inverse of [`convert_x`]
+/// and [`convert_y`].
+fn world_to_position(point: Vec2, tile_size: f32, arena: &Arena) -> Position {
+    Position {
+        x: unconvert(point.x, arena.width as f32, tile_size),
+        y: unconvert(
+            point.y,
+            (arena.height + SCORE_BOARD_HEIGHT) as f32,
+            tile_size,
+        ),
+    }
+}
+
+fn position_translation(
+    windows: Res<Windows>,
+    arena: Res<Arena>,
+    mut q: Query<(&Position, &mut Transform)>,
+) {
     let window = windows.get_primary().unwrap();
+    let tile_size = arena.tile_size(window);
     for (pos, mut transform) in q.iter_mut() {
         transform.translation = Vec3::new(
-            convert_x(pos.x, window.width()),
-            convert_y(pos.y, window.height()),
+            convert_x(pos.x, tile_size, &arena),
+            convert_y(pos.y, tile_size, &arena),
             0.0,
         );
     }
 }
 
-fn rotation_translation(mut q: Query<(&mut Transform, &Rocket)>) {
-    for (mut transform, rocket) in q.iter_mut() {
-        transform.rotation = match rocket.direction {
+fn hud_layout(arena: Res<Arena>, mut q: Query<(&HudPosition, &mut Position)>) {
+    for (hud_position, mut pos) in q.iter_mut() {
+        let (numerator, denominator) = hud_position.column;
+        pos.x = arena.width as i32 / denominator * numerator + hud_position.offset;
+        pos.y = arena.height as i32 + hud_position.row;
+    }
+}
+
+/// A control sprite and the key pictured on it.
+type ControlSprite<'a> = Option<(&'a ControlButton, &'a ControlPicture)>;
+
+/// Control sprites are only shown while the key they picture is bound.
+fn hud_visibility(
+    state: Res<State<AppState>>,
+    action_map: Res<ActionMap>,
+    mut q: Query<(&mut Visible, ControlSprite), With<HudPosition>>,
+) {
+    let in_level = matches!(
+        state.current(),
+        AppState::Playing | AppState::Paused | AppState::GameOver
+    );
+    for (mut visible, control) in q.iter_mut() {
+        let pictured_key_bound = match control {
+            Some((ControlButton(action), ControlPicture(key))) => {
+                action_map.keys(*action).contains(key)
+            }
+            None => true,
+        };
+        visible.is_visible = in_level && pictured_key_bound;
+    }
+}
+
+fn rocket_position(simulation: Res<Simulation>, mut q: Query<(&mut Position, &mut Rocket)>) {
+    for (mut pos, mut rocket) in q.iter_mut() {
+        *pos = simulation.position();
+        if rocket.keys != simulation.keys() {
+            rocket.keys = simulation.keys().to_vec();
+        }
+    }
+}
+
+/// Keys and doors disappear while the rocket holds the key of their colour.
+fn keys_and_doors(rockets: Query<&Rocket>, mut q: Query<(&Keyed, &mut Visible)>) {
+    let keys: Vec<KeyColor> = rockets
+        .iter()
+        .flat_map(|rocket| rocket.keys.iter().copied())
+        .collect();
+    for (keyed, mut visible) in q.iter_mut() {
+        visible.is_visible = !keys.contains(&keyed.color);
+    }
+}
+
+fn rotation_translation(simulation: Res<Simulation>, mut q: Query<&mut Transform, With<Rocket>>) {
+    for mut transform in q.iter_mut() {
+        transform.rotation = match simulation.direction() {
             Direction::Right => Quat::from_rotation_z(0.0),
             Direction::Down => Quat::from_rotation_z(-std::f32::consts::PI * 0.5),
             Direction::Left => Quat::from_rotation_z(std::f32::consts::PI),
@@ -700,41 +1107,77 @@ fn rotation_translation(mut q: Query<(&mut Transform, &Rocket)>) {
 
 fn reached_target(
     mut reader: EventReader<TargetEvent>,
-    mut rocket_query: Query<(&mut Rocket, &mut Position)>,
+    mut simulation: ResMut<Simulation>,
     mut segments: ResMut<RocketPath>,
-    mut next_level_writer: EventWriter<NextLevelEvent>,
-    level_info: Res<LevelInfo>,
+    mut state: ResMut<State<AppState>>,
+    mut level_info: ResMut<LevelInfo>,
+    history: Res<RunHistory>,
+    mut save: ResMut<SaveData>,
+    mut input_buffer: ResMut<InputBuffer>,
     mut find_path_event: EventWriter<FindPathEvent>,
 ) {
     if reader.iter().next().is_some() {
-        //TODO: update score
-        if let Some((mut rocket, mut rocket_pos)) = rocket_query.iter_mut().next() {
-            rocket.direction = Direction::StandStill;
-            rocket.turns_left = MAX_TURNS;
-            rocket_pos.x = 1;
-            rocket_pos.y = 1;
-            segments.0.clear();
-            segments.0.push(rocket_pos.clone());
-            segments.1.clear();
-
-            if level_info.counter_completion > 2 {
-                next_level_writer.send(NextLevelEvent {});
+        simulation.restart();
+        *segments = RocketPath::default();
+        input_buffer.0.clear();
+
+        if level_info.counter_completion >= level_info.required_completions {
+            let score = Score {
+                turns: level_info.turns_used,
+                runs: level_info.runs_used,
+                required_runs: level_info.required_completions,
+                walls: history.0.iter().map(|run| run.walls.len() as u32).sum(),
+            };
+            level_info.score = Some(score);
+            let result = BestResult::new(score, level_info.par);
+            save.record_clear(level_info.current_level, result);
+            storage::store(&save);
+            let next = if level_info.current_level + 1 < level_info.level_count {
+                AppState::LevelComplete
             } else {
-                find_path_event.send(FindPathEvent {});
-            }
+                AppState::Victory
+            };
+            let _ = state.set(next);
+        } else {
+            find_path_event.send(FindPathEvent {});
         }
     }
 }
 
-fn scoreboard_system(mut rocket_query: Query<&Rocket>, mut query: Query<&mut Text>) {
-    if let Some(rocket) = rocket_query.iter_mut().next() {
-        let mut text = query.single_mut().unwrap();
-        text.sections[0].value = format!("turns left: {}", rocket.turns_left);
+fn scoreboard_system(
+    simulation: Res<Simulation>,
+    level_info: Res<LevelInfo>,
+    action_map: Res<ActionMap>,
+    mut query: Query<(&mut Text, &HudText)>,
+) {
+    for (mut text, hud_text) in query.iter_mut() {
+        text.sections[0].value = match hud_text {
+            HudText::TurnsLeft => format!("turns left: {}", simulation.turns_left()),
+            HudText::Runs => format!(
+                "runs: {}/{}",
+                level_info.counter_completion, level_info.required_completions
+            ),
+            HudText::Hints => match level_info.hint_note {
+                Some(note) => note.to_string(),
+                None => format!("hints: {}", level_info.hints_used),
+            },
+            HudText::Binding(action, pictured) => {
+                if action_map.keys(*action).contains(pictured) {
+                    String::new()
+                } else {
+                    action_map.label(*action)
+                }
+            }
+        };
     }
 }
 
-fn reset_input(keyboard_input: Res<Input<KeyCode>>, mut reset_writer: EventWriter<ResetEvent>) {
-    if keyboard_input.pressed(KeyCode::R) {
+fn reset_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    action_map: Res<ActionMap>,
+    mut reset_writer: EventWriter<ResetEvent>,
+) {
+    if action_map.just_pressed(&keyboard_input, Action::Reset) {
         reset_writer.send(ResetEvent {});
     }
 }
@@ -743,73 +1186,197 @@ fn reset_last_one(
     mut commands: Commands,
     mut reader: EventReader<ResetEvent>,
     mut target_writer: EventWriter<TargetEvent>,
+    mut simulation: ResMut<Simulation>,
+    mut grid: ResMut<Grid>,
     mut rocket_path: ResMut<RocketPath>,
+    mut level_info: ResMut<LevelInfo>,
 ) {
     if reader.iter().next().is_some() {
-        for i in 0..rocket_path.1.len() {
-            let wall = rocket_path.1[i];
+        level_info.end_run(&simulation);
+        simulation.reset(&mut grid.0);
+        for (wall, _) in rocket_path.walls.drain(..) {
             commands.entity(wall).despawn();
         }
-        rocket_path.1.clear();
 
         target_writer.send(TargetEvent {});
     }
 }
 
-use petgraph::algo::dijkstra;
-use petgraph::graph::{NodeIndex, UnGraph};
+fn undo_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    action_map: Res<ActionMap>,
+    mut undo_writer: EventWriter<UndoEvent>,
+) {
+    if action_map.just_pressed(&keyboard_input, Action::Undo) {
+        undo_writer.send(UndoEvent {});
+    }
+}
 
-fn path_finder(
-    wall_query: Query<&Position, With<Wall>>,
-    target_query: Query<&Position, With<Target>>,
-    mut reader: EventReader<FindPathEvent>,
-    mut game_over_writer: EventWriter<GameOverEvent>,
+/// Abandons the current run and takes back the last completed one,
+/// removing the walls both of them left behind and repairing the cracked
+/// walls they hit.
+fn undo_run(
+    mut commands: Commands,
+    mut reader: EventReader<UndoEvent>,
+    mut target_writer: EventWriter<TargetEvent>,
+    mut simulation: ResMut<Simulation>,
+    mut grid: ResMut<Grid>,
+    mut rocket_path: ResMut<RocketPath>,
+    mut history: ResMut<RunHistory>,
+    mut level_info: ResMut<LevelInfo>,
 ) {
     if reader.iter().next().is_some() {
-        for target_position in target_query.iter() {
-            let target_node: u32 =
-                target_position.x as u32 * ARENA_WIDTH + target_position.y as u32;
-            let mut array = [[true; ARENA_HEIGHT as usize]; ARENA_WIDTH as usize];
+        level_info.end_run(&simulation);
+        simulation.reset(&mut grid.0);
+        for (wall, _) in rocket_path.walls.drain(..) {
+            commands.entity(wall).despawn();
+        }
 
-            for wall_position in wall_query.iter() {
-                array[wall_position.x as usize][wall_position.y as usize] = false;
+        if let Some(run) = history.0.pop() {
+            for (wall, pos) in run.walls {
+                grid.0.set_tile(pos, Tile::Empty);
+                commands.entity(wall).despawn();
             }
-
-            let mut edges: Vec<(u32, u32)> = vec![];
-            for x in 1..ARENA_WIDTH - 1 {
-                for y in 1..ARENA_HEIGHT - 1 {
-                    if array[x as usize][y as usize] && array[x as usize + 1][y as usize] {
-                        edges.push((x * ARENA_WIDTH + y, (x + 1) * ARENA_WIDTH + y));
-                    }
-                    if array[x as usize][y as usize] && array[x as usize][y as usize + 1] {
-                        edges.push((x * ARENA_WIDTH + y, x * ARENA_WIDTH + y + 1));
-                    }
-                }
+            for &pos in run.hits.iter().rev() {
+                grid.0.repair(pos);
             }
+            level_info.counter_completion -= 1;
+        }
+
+        target_writer.send(TargetEvent {});
+    }
+}
 
-            let g = UnGraph::<i32, ()>::from_edges(&edges);
+fn hint_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    action_map: Res<ActionMap>,
+    mut hint_writer: EventWriter<HintEvent>,
+) {
+    if action_map.just_pressed(&keyboard_input, Action::Hint) {
+        hint_writer.send(HintEvent {});
+    }
+}
 
-            // Find the shortest path from source to tarte using `1` as the cost for every edge.
-            let node_map = dijkstra(
-                &g,
-                (ARENA_WIDTH + 1).into(),
-                Some(target_node.into()),
-                |_| 1,
-            );
-            if node_map.contains_key(&NodeIndex::new(target_node as usize)) {
-                return;
+/// Searches for runs that clear the level from here, so following hints
+/// never leaves a later run stuck.
+fn request_hint(
+    grid: Res<Grid>,
+    simulation: Res<Simulation>,
+    level_info: Res<LevelInfo>,
+    mut hint_search: ResMut<HintSearch>,
+    mut reader: EventReader<HintEvent>,
+) {
+    if reader.iter().next().is_some() && level_info.loaded {
+        let runs = level_info.required_completions - level_info.counter_completion;
+        let planner = Planner::new(&grid.0, &simulation, runs);
+        hint_search.0 = Some((planner, simulation.turns_left()));
+    }
+}
+
+/// Marks the next turn the search of [`request_hint`] found. If there is no
+/// turn to take, the scoreboard says why instead.
+fn show_hint(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    hint_query: Query<Entity, With<Hint>>,
+    simulation: Res<Simulation>,
+    mut hint_search: ResMut<HintSearch>,
+    mut level_info: ResMut<LevelInfo>,
+) {
+    let (outlook, turns_left) = match &mut hint_search.0 {
+        Some((planner, turns_left)) => (planner.resume(SEARCH_STEPS), *turns_left),
+        None => return,
+    };
+    if outlook == Outlook::Unknown {
+        return;
+    }
+    hint_search.0 = None;
+    // The rocket turned while the search ran, so its turn may be gone.
+    if turns_left != simulation.turns_left() {
+        return;
+    }
+    for hint in hint_query.iter() {
+        commands.entity(hint).despawn();
+    }
+    level_info.hint_note = match outlook {
+        Outlook::Solvable(runs) => match runs[0].first() {
+            Some(&hint) => {
+                spawn_hint(&mut commands, &mut materials, hint, turns_left);
+                level_info.hints_used += 1;
+                None
             }
+            None => Some("straight"),
+        },
+        _ => Some("dead end"),
+    };
+}
+
+fn clear_hint(
+    mut commands: Commands,
+    hint_query: Query<(Entity, &Hint)>,
+    simulation: Res<Simulation>,
+    mut hint_search: ResMut<HintSearch>,
+    mut level_info: ResMut<LevelInfo>,
+    mut reader: EventReader<TargetEvent>,
+) {
+    let run_over = reader.iter().next().is_some();
+    if run_over {
+        *hint_search = HintSearch::default();
+        level_info.hint_note = None;
+    }
+    for (entity, hint) in hint_query.iter() {
+        if run_over || hint.turns_left != simulation.turns_left() {
+            commands.entity(entity).despawn();
         }
+    }
+}
 
-        game_over_writer.send(GameOverEvent {});
+/// Ends the game once the runs left can not all reach a target any more.
+/// Unless the targets are out of reach altogether, that takes a search over
+/// a few frames, the player can go on meanwhile.
+fn path_finder(
+    grid: Res<Grid>,
+    simulation: Res<Simulation>,
+    level_info: Res<LevelInfo>,
+    mut path_finder: ResMut<PathFinder>,
+    mut reader: EventReader<FindPathEvent>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if reader.iter().next().is_some() {
+        if !grid.0.target_reachable(simulation.start()) {
+            path_finder.0 = None;
+            let _ = state.push(AppState::GameOver);
+            return;
+        }
+        let runs = level_info.required_completions - level_info.counter_completion;
+        path_finder.0 = Some(Planner::new(&grid.0, &simulation, runs));
+    }
+
+    let outlook = match &mut path_finder.0 {
+        Some(planner) => planner.resume(SEARCH_STEPS),
+        None => return,
+    };
+    if outlook != Outlook::Unknown {
+        path_finder.0 = None;
     }
+    if outlook == Outlook::Unsolvable {
+        let _ = state.push(AppState::GameOver);
+    }
+}
+
+fn load_save_data(mut commands: Commands) {
+    commands.insert_resource(storage::load());
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 fn audio_system(
     asset_server: Res<AssetServer>,
     audio: Res<Audio>,
+    save: Res<SaveData>,
 ) {
+    if !save.settings.music {
+        return;
+    }
     let music_handle = asset_server.load("parallel_universes.mp3");
     audio.play(music_handle);
 }
@@ -823,63 +1390,198 @@ fn main() {
             height: WINDOW_HEIGHT as f32,
             ..Default::default()
         })
+        .insert_resource(InputBuffer::default())
+        .insert_resource(touch::PointerGesture::default())
+        .insert_resource(gamepad::Gamepads::default())
         .insert_resource(RocketPath::default())
+        .insert_resource(RunHistory::default())
+        .insert_resource(PathFinder::default())
+        .insert_resource(HintSearch::default())
         .insert_resource(LevelInfo::default())
+        .insert_resource(Arena::default())
+        .insert_resource(Simulation::new(Position::default(), 0))
+        .insert_resource(Grid::default())
+        .insert_resource(LevelSelection::default())
+        .insert_resource(ControlsSelection::default())
+        .insert_resource(ActionMap::default())
+        .add_startup_system_to_stage(StartupStage::PreStartup, load_save_data.system())
         .add_startup_system(setup.system())
         .add_startup_system(setup_scoreboard.system())
-        .add_startup_stage("game_setup", SystemStage::single(spawn_rocket.system()))
+        .add_state(AppState::Title)
         .add_system(scoreboard_system.system())
-        .add_system(
-            rocket_movement_input
-                .system()
-                .label(RocketMovement::Input)
-                .before(RocketMovement::Movement),
+        .add_system(hud_layout.system())
+        .add_system(hud_visibility.system())
+        .add_system(gamepad::gamepad_connections.system())
+        .add_system(teleporter_sprites.system())
+        .add_system(gate_sprites.system())
+        .add_system(cracked_walls.system())
+        .add_system_to_stage(CoreStage::PreUpdate, actions::update_action_map.system())
+        .add_system(rocket_position.system().after(RocketMovement::Target))
+        .add_system(keys_and_doors.system())
+        .add_system_set(
+            SystemSet::on_enter(AppState::Title).with_system(screens::setup_title.system()),
+        )
+        .add_system_set(SystemSet::on_update(AppState::Title).with_system(screens::title.system()))
+        .add_system_set(
+            SystemSet::on_exit(AppState::Title).with_system(screens::despawn_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::LevelSelect)
+                .with_system(screens::setup_level_select.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::LevelSelect).with_system(screens::level_select.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::LevelSelect).with_system(screens::despawn_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Controls).with_system(screens::setup_controls.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Controls).with_system(screens::controls.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Controls).with_system(screens::despawn_screen.system()),
+        )
+        .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(start_level.system()))
+        .add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(spawn_level.system().before(RocketMovement::Input))
+                .with_system(
+                    rocket_movement_input
+                        .system()
+                        .label(RocketMovement::Input)
+                        .before(RocketMovement::Movement),
+                )
+                .with_system(
+                    reached_target
+                        .system()
+                        .label(RocketMovement::Target)
+                        .after(RocketMovement::Movement),
+                )
+                .with_system(
+                    reset_input
+                        .system()
+                        .label(RocketMovement::Reset)
+                        .after(RocketMovement::Movement),
+                )
+                .with_system(
+                    gamepad::gamepad_input
+                        .system()
+                        .label(RocketMovement::Input)
+                        .label(RocketMovement::Reset)
+                        .before(RocketMovement::Movement),
+                )
+                .with_system(
+                    touch::pointer_input
+                        .system()
+                        .label(RocketMovement::Input)
+                        .label(RocketMovement::Gesture)
+                        .before(RocketMovement::Movement),
+                )
+                .with_system(
+                    mouse::mouse_input
+                        .system()
+                        .label(RocketMovement::Input)
+                        .after(RocketMovement::Gesture)
+                        .before(RocketMovement::Movement),
+                )
+                .with_system(
+                    touch::control_buttons
+                        .system()
+                        .label(RocketMovement::Input)
+                        .label(RocketMovement::Reset)
+                        .after(RocketMovement::Gesture)
+                        .before(RocketMovement::Movement),
+                )
+                .with_system(reset_last_one.system().after(RocketMovement::Reset))
+                .with_system(
+                    undo_input
+                        .system()
+                        .label(RocketMovement::Reset)
+                        .after(RocketMovement::Movement),
+                )
+                .with_system(undo_run.system().after(RocketMovement::Reset))
+                .with_system(hint_input.system().label(RocketMovement::Input))
+                .with_system(request_hint.system().after(RocketMovement::Input))
+                .with_system(show_hint.system().after(RocketMovement::Input))
+                .with_system(clear_hint.system().after(RocketMovement::Reset))
+                .with_system(
+                    path_finder
+                        .system()
+                        .label(RocketMovement::Path)
+                        .after(RocketMovement::Target)
+                        .after(RocketMovement::Reset),
+                )
+                .with_system(screens::pause_input.system().after(RocketMovement::Path)),
         )
         .add_system_set(
             SystemSet::new()
                 .with_run_criteria(FixedTimestep::step(0.10))
                 .with_system(rocket_movement.system().label(RocketMovement::Movement)),
         )
-        .add_system(
-            reached_target
-                .system()
-                .label(RocketMovement::Target)
-                .after(RocketMovement::Movement),
+        .add_system_set(SystemSet::on_exit(AppState::Playing).with_system(despawn_level.system()))
+        .add_system_set(
+            SystemSet::on_enter(AppState::Paused).with_system(screens::setup_paused.system()),
         )
-        .add_system(
-            reset_input
-                .system()
-                .label(RocketMovement::Reset)
-                .after(RocketMovement::Movement),
+        .add_system_set(
+            SystemSet::on_update(AppState::Paused).with_system(screens::paused.system()),
         )
-        .add_system(reset_last_one.system().after(RocketMovement::Reset))
-        .add_system(
-            load_next_level
-                .system()
-                .label(RocketMovement::Loading)
-                .after(RocketMovement::Target),
+        .add_system_set(
+            SystemSet::on_exit(AppState::Paused).with_system(screens::despawn_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::LevelComplete)
+                .with_system(screens::setup_level_complete.system()),
         )
-        .add_system(
-            path_finder
-                .system()
-                .label(RocketMovement::Path)
-                .after(RocketMovement::Loading)
-                .after(RocketMovement::Reset),
+        .add_system_set(
+            SystemSet::on_update(AppState::LevelComplete)
+                .with_system(screens::level_complete.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::LevelComplete)
+                .with_system(screens::despawn_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::GameOver).with_system(screens::setup_game_over.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::GameOver).with_system(screens::game_over.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::GameOver).with_system(screens::despawn_screen.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Victory).with_system(screens::setup_victory.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Victory).with_system(screens::back_to_title.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Victory)
+                .with_system(screens::despawn_screen.system())
+                .with_system(despawn_level.system()),
         )
-        .add_system(load_game_over.system().after(RocketMovement::Path))
         .add_system_set_to_stage(
             CoreStage::PostUpdate,
             SystemSet::new()
                 .with_system(position_translation.system())
                 .with_system(rotation_translation.system())
-                .with_system(size_scaling.system()),
+                .with_system(size_scaling.system())
+                .with_system(atlas_scaling.system()),
         )
         .add_event::<TargetEvent>()
         .add_event::<ResetEvent>()
-        .add_event::<NextLevelEvent>()
+        .add_event::<UndoEvent>()
         .add_event::<FindPathEvent>()
-        .add_event::<GameOverEvent>()
-        .add_plugins(DefaultPlugins);
+        .add_event::<HintEvent>()
+        .add_event::<touch::Tap>()
+        .add_plugins(DefaultPlugins)
+        .add_asset::<LevelData>()
+        .add_asset::<LevelManifest>()
+        .init_asset_loader::<LevelLoader>()
+        .init_asset_loader::<LevelManifestLoader>();
 
     #[cfg(target_arch = "wasm32")]
     app.add_plugin(bevy_webgl2::WebGL2Plugin);
@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+
+use butterfly_effect::{Direction, Simulation};
+
+use crate::touch::Tap;
+use crate::{world_to_position, Arena, Grid, InputBuffer};
+
+/// Clicking a tile in the same row or column as the rocket steers it towards
+/// that tile. Clicks on the rocket itself or off the board are ignored.
+pub fn mouse_input(
+    mut reader: EventReader<Tap>,
+    windows: Res<Windows>,
+    arena: Res<Arena>,
+    grid: Res<Grid>,
+    simulation: Res<Simulation>,
+    mut input_buffer: ResMut<InputBuffer>,
+) {
+    let window = windows.get_primary().unwrap();
+    for Tap(point) in reader.iter() {
+        let clicked = world_to_position(*point, arena.tile_size(window), &arena);
+        if !grid.0.contains(clicked) {
+            continue;
+        }
+
+        let rocket = simulation.position();
+        let dir = if clicked.y == rocket.y && clicked.x < rocket.x {
+            Direction::Left
+        } else if clicked.y == rocket.y && clicked.x > rocket.x {
+            Direction::Right
+        } else if clicked.x == rocket.x && clicked.y < rocket.y {
+            Direction::Down
+        } else if clicked.x == rocket.x && clicked.y > rocket.y {
+            Direction::Up
+        } else {
+            continue;
+        };
+        input_buffer.push(dir);
+    }
+}
@@ -0,0 +1,24 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// Which levels the player cleared. The first level is always unlocked,
+/// every other level once the one before it is cleared.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    cleared: BTreeSet<usize>,
+}
+
+impl Progress {
+    pub fn clear(&mut self, level: usize) {
+        self.cleared.insert(level);
+    }
+
+    pub fn is_cleared(&self, level: usize) -> bool {
+        self.cleared.contains(&level)
+    }
+
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level == 0 || self.is_cleared(level - 1)
+    }
+}
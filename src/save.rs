@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bindings::Bindings;
+use crate::progress::Progress;
+use crate::score::Score;
+
+/// Bumped whenever the layout of [`SaveData`] changes. [`SaveData::parse`]
+/// upgrades saves written by older versions of the game.
+pub const SAVE_VERSION: u32 = 4;
+
+/// The best results of a cleared level. Each one is kept on its own, so
+/// they may come from different clears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BestResult {
+    /// Turns spent over all runs of the level, including abandoned ones.
+    pub fewest_turns: u32,
+    /// Runs started, including the ones that were reset or undone.
+    pub fewest_runs: u32,
+    /// Corner walls left on the board when the level was cleared.
+    pub fewest_walls: u32,
+    pub stars: u32,
+}
+
+impl BestResult {
+    pub fn new(score: Score, par: u32) -> Self {
+        BestResult {
+            fewest_turns: score.turns,
+            fewest_runs: score.runs,
+            fewest_walls: score.walls,
+            stars: score.stars(par),
+        }
+    }
+
+    /// Keeps the better of each result.
+    fn merge(&mut self, other: &BestResult) {
+        self.fewest_turns = self.fewest_turns.min(other.fewest_turns);
+        self.fewest_runs = self.fewest_runs.min(other.fewest_runs);
+        self.fewest_walls = self.fewest_walls.min(other.fewest_walls);
+        self.stars = self.stars.max(other.stars);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    pub music: bool,
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            music: true,
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+/// Everything that survives a restart of the game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub progress: Progress,
+    /// Best results by level index.
+    pub best: BTreeMap<usize, BestResult>,
+    pub settings: Settings,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        SaveData {
+            version: SAVE_VERSION,
+            progress: Progress::default(),
+            best: BTreeMap::new(),
+            settings: Settings::default(),
+        }
+    }
+}
+
+/// Just the version, to pick the layout to read the rest with.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// Version 1 did not score levels yet.
+mod v1 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use crate::progress::Progress;
+
+    #[derive(Deserialize)]
+    pub struct BestResult {
+        pub turns: u32,
+        pub runs: u32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SaveData {
+        pub progress: Progress,
+        pub best: BTreeMap<usize, BestResult>,
+        pub settings: super::v2::Settings,
+    }
+
+    /// Walls were not counted, so old results keep a single star until the
+    /// level is cleared again.
+    pub fn migrate(save: SaveData) -> super::v2::SaveData {
+        let best = save
+            .best
+            .into_iter()
+            .map(|(level, result)| {
+                let result = super::v3::BestResult {
+                    turns: result.turns,
+                    runs: result.runs,
+                    walls: 0,
+                    stars: 1,
+                };
+                (level, result)
+            })
+            .collect();
+        super::v2::SaveData {
+            progress: save.progress,
+            best,
+            settings: save.settings,
+        }
+    }
+}
+
+/// Version 2 had no key bindings.
+mod v2 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use crate::bindings::Bindings;
+    use crate::progress::Progress;
+
+    #[derive(Deserialize)]
+    pub struct Settings {
+        pub music: bool,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SaveData {
+        pub progress: Progress,
+        pub best: BTreeMap<usize, super::v3::BestResult>,
+        pub settings: Settings,
+    }
+
+    pub fn migrate(save: SaveData) -> super::v3::SaveData {
+        super::v3::SaveData {
+            progress: save.progress,
+            best: save.best,
+            settings: super::Settings {
+                music: save.settings.music,
+                bindings: Bindings::default(),
+            },
+        }
+    }
+}
+
+/// Version 3 kept the turns, runs and walls of the single best clear.
+mod v3 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use crate::progress::Progress;
+
+    #[derive(Deserialize)]
+    pub struct BestResult {
+        pub turns: u32,
+        pub runs: u32,
+        pub walls: u32,
+        pub stars: u32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct SaveData {
+        pub progress: Progress,
+        pub best: BTreeMap<usize, BestResult>,
+        pub settings: super::Settings,
+    }
+
+    pub fn migrate(save: SaveData) -> super::SaveData {
+        let best = save
+            .best
+            .into_iter()
+            .map(|(level, result)| {
+                let result = super::BestResult {
+                    fewest_turns: result.turns,
+                    fewest_runs: result.runs,
+                    fewest_walls: result.walls,
+                    stars: result.stars,
+                };
+                (level, result)
+            })
+            .collect();
+        super::SaveData {
+            version: super::SAVE_VERSION,
+            progress: save.progress,
+            best,
+            settings: save.settings,
+        }
+    }
+}
+
+impl SaveData {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let header: Header = ron::from_str(source)?;
+        match header.version {
+            1 => Ok(v3::migrate(v2::migrate(v1::migrate(ron::from_str(
+                source,
+            )?)))),
+            2 => Ok(v3::migrate(v2::migrate(ron::from_str(source)?))),
+            3 => Ok(v3::migrate(ron::from_str(source)?)),
+            SAVE_VERSION => Ok(ron::from_str(source)?),
+            version if version > SAVE_VERSION => anyhow::bail!(
+                "save data version {} is newer than this game supports ({})",
+                version,
+                SAVE_VERSION
+            ),
+            version => anyhow::bail!("unknown save data version {}", version),
+        }
+    }
+
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    /// Marks `level` as cleared and keeps the better of each of the stored
+    /// and the new results.
+    pub fn record_clear(&mut self, level: usize, result: BestResult) {
+        self.progress.clear(level);
+        self.best.entry(level).or_insert(result).merge(&result);
+    }
+}
@@ -0,0 +1,28 @@
+/// How well a level was cleared. Every turn, every run beyond the required
+/// ones and every corner wall left on the board costs a point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub turns: u32,
+    pub runs: u32,
+    pub required_runs: u32,
+    pub walls: u32,
+}
+
+impl Score {
+    pub fn points(&self) -> u32 {
+        self.turns + self.runs.saturating_sub(self.required_runs) + self.walls
+    }
+
+    /// Three stars at or below `par`, two up to half as much again and one
+    /// for any other clear.
+    pub fn stars(&self, par: u32) -> u32 {
+        let points = self.points();
+        if points <= par {
+            3
+        } else if points <= par + par / 2 {
+            2
+        } else {
+            1
+        }
+    }
+}
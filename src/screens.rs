@@ -0,0 +1,467 @@
+use bevy::prelude::*;
+
+use butterfly_effect::{Action, Board, Position, SaveData};
+
+use crate::actions::{self, ActionMap};
+use crate::assets::{LevelData, LevelManifest};
+use crate::storage;
+use crate::{spawn_board, Arena, Grid, LevelAssets, LevelInfo, UndoEvent};
+
+/// The screens of the game. `Paused` and `GameOver` are pushed on top of
+/// `Playing`, so the level stays loaded while the game is paused and the
+/// last run can still be undone once the game is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppState {
+    Title,
+    LevelSelect,
+    Controls,
+    Playing,
+    Paused,
+    LevelComplete,
+    GameOver,
+    Victory,
+}
+
+/// Text spawned by a screen, despawned when the screen is left.
+pub struct ScreenText {}
+
+/// The level highlighted on the level select screen.
+#[derive(Default)]
+pub struct LevelSelection(usize);
+
+/// Levels per row on the level select screen.
+const LEVEL_SELECT_COLUMNS: usize = 5;
+
+/// The action highlighted on the controls screen, and whether the next key
+/// pressed is bound to it.
+#[derive(Default)]
+pub struct ControlsSelection {
+    index: usize,
+    rebinding: bool,
+}
+
+fn spawn_screen_text(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    value: String,
+    position: Position,
+) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font: asset_server.load("fonts/press-start/prstart.ttf"),
+                    font_size: 20.0,
+                    color: Color::rgb(1.0, 0.8, 0.0),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            ..Default::default()
+        })
+        .insert(ScreenText {})
+        .insert(position);
+}
+
+/// Earned stars as `*`, missing ones as `-`.
+fn stars(count: u32) -> String {
+    (0..3)
+        .map(|star| if star < count { "*" } else { "-" })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn center(arena: &Arena) -> Position {
+    Position {
+        x: arena.width as i32 / 2,
+        y: arena.height as i32 / 2,
+    }
+}
+
+pub fn despawn_screen(mut commands: Commands, query: Query<Entity, With<ScreenText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn setup_title(mut commands: Commands, asset_server: Res<AssetServer>, arena: Res<Arena>) {
+    spawn_screen_text(
+        &mut commands,
+        &asset_server,
+        "BUTTERFLY EFFECT\n\n\nENTER  play\n\nL  level select\n\nC  controls".to_string(),
+        center(&arena),
+    );
+}
+
+pub fn title(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut level_info: ResMut<LevelInfo>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.reset(KeyCode::Return);
+        level_info.current_level = 0;
+        state.set(AppState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::L) {
+        keyboard_input.reset(KeyCode::L);
+        state.set(AppState::LevelSelect).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::C) {
+        keyboard_input.reset(KeyCode::C);
+        state.set(AppState::Controls).unwrap();
+    }
+}
+
+pub fn setup_level_select(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    level_info: Res<LevelInfo>,
+    mut selection: ResMut<LevelSelection>,
+) {
+    selection.0 = level_info.current_level;
+    spawn_screen_text(&mut commands, &asset_server, String::new(), center(&arena));
+}
+
+/// Shows every level of the manifest as a grid. Locked levels can be
+/// selected to see that they are locked, but not played.
+pub fn level_select(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut level_info: ResMut<LevelInfo>,
+    mut selection: ResMut<LevelSelection>,
+    save: Res<SaveData>,
+    level_assets: Res<LevelAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    levels: Res<Assets<LevelData>>,
+    mut query: Query<&mut Text, With<ScreenText>>,
+) {
+    let manifest = match manifests.get(&level_assets.manifest) {
+        Some(manifest) => manifest,
+        None => return,
+    };
+    let level_count = manifest.levels.len();
+    if level_count == 0 {
+        return;
+    }
+    selection.0 = selection.0.min(level_count - 1);
+
+    if keyboard_input.just_pressed(KeyCode::Left) && selection.0 > 0 {
+        selection.0 -= 1;
+    } else if keyboard_input.just_pressed(KeyCode::Right) && selection.0 + 1 < level_count {
+        selection.0 += 1;
+    } else if keyboard_input.just_pressed(KeyCode::Up) && selection.0 >= LEVEL_SELECT_COLUMNS {
+        selection.0 -= LEVEL_SELECT_COLUMNS;
+    } else if keyboard_input.just_pressed(KeyCode::Down)
+        && selection.0 + LEVEL_SELECT_COLUMNS < level_count
+    {
+        selection.0 += LEVEL_SELECT_COLUMNS;
+    } else if keyboard_input.just_pressed(KeyCode::Return) && save.progress.is_unlocked(selection.0)
+    {
+        keyboard_input.reset(KeyCode::Return);
+        level_info.current_level = selection.0;
+        state.set(AppState::Playing).unwrap();
+        return;
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Title).unwrap();
+        return;
+    }
+
+    let mut value = "SELECT LEVEL\n\n".to_string();
+    for (row, handles) in manifest.levels.chunks(LEVEL_SELECT_COLUMNS).enumerate() {
+        for column in 0..handles.len() {
+            let index = row * LEVEL_SELECT_COLUMNS + column;
+            let label = if save.progress.is_unlocked(index) {
+                format!("{:>2}", index + 1)
+            } else {
+                "--".to_string()
+            };
+            if index == selection.0 {
+                value.push_str(&format!("[{}]", label));
+            } else {
+                value.push_str(&format!(" {} ", label));
+            }
+        }
+        value.push('\n');
+    }
+
+    let selected = if save.progress.is_unlocked(selection.0) {
+        let name = levels
+            .get(&manifest.levels[selection.0])
+            .map(|level_data| level_data.level.name.clone())
+            .unwrap_or_default();
+        match save.best.get(&selection.0) {
+            Some(best) => format!("{}  {}", name, stars(best.stars)),
+            None => name,
+        }
+    } else {
+        "locked".to_string()
+    };
+    value.push_str(&format!("\n{}\n\nENTER  play\nESC  back", selected));
+    for mut text in query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+pub fn setup_controls(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    mut selection: ResMut<ControlsSelection>,
+) {
+    *selection = ControlsSelection::default();
+    spawn_screen_text(&mut commands, &asset_server, String::new(), center(&arena));
+}
+
+/// Lists the keys bound to every action. Rebinding replaces all keys of the
+/// action with the one pressed next, see [`Bindings::rebind`]; Escape
+/// cancels, so it can only be bound again by restoring the defaults.
+///
+/// [`Bindings::rebind`]: butterfly_effect::Bindings::rebind
+pub fn controls(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut selection: ResMut<ControlsSelection>,
+    mut save: ResMut<SaveData>,
+    mut query: Query<&mut Text, With<ScreenText>>,
+) {
+    let selected = Action::ALL[selection.index];
+    if selection.rebinding {
+        if keyboard_input.just_pressed(KeyCode::Escape) {
+            keyboard_input.reset(KeyCode::Escape);
+            selection.rebinding = false;
+        } else if let Some(key) = actions::just_pressed_key(&keyboard_input) {
+            keyboard_input.reset(key);
+            save.settings
+                .bindings
+                .rebind(selected, &actions::key_name(key));
+            storage::store(&save);
+            selection.rebinding = false;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Up) && selection.index > 0 {
+        selection.index -= 1;
+    } else if keyboard_input.just_pressed(KeyCode::Down) && selection.index + 1 < Action::ALL.len()
+    {
+        selection.index += 1;
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.reset(KeyCode::Return);
+        selection.rebinding = true;
+    } else if keyboard_input.just_pressed(KeyCode::Back) {
+        keyboard_input.reset(KeyCode::Back);
+        save.settings.bindings = Default::default();
+        storage::store(&save);
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Title).unwrap();
+        return;
+    }
+
+    let mut value = "CONTROLS\n\n".to_string();
+    for (index, &action) in Action::ALL.iter().enumerate() {
+        let keys = save.settings.bindings.keys(action);
+        let keys = if keys.is_empty() {
+            "-".to_string()
+        } else {
+            keys.iter()
+                .map(|name| actions::label(name))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let marker = if index == selection.index { ">" } else { " " };
+        value.push_str(&format!("{} {:<6} {:<12}\n", marker, action.name(), keys));
+    }
+    if selection.rebinding {
+        value.push_str(&format!(
+            "\npress a key for {}\nENTER and Q are reserved\nESC  cancel",
+            Action::ALL[selection.index].name()
+        ));
+    } else {
+        value.push_str("\nENTER  rebind\nBACKSPACE  defaults\nESC  back");
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+pub fn pause_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    action_map: Res<ActionMap>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if action_map.just_pressed(&keyboard_input, Action::Pause) {
+        action_map.reset(&mut keyboard_input, Action::Pause);
+        let _ = state.push(AppState::Paused);
+    }
+}
+
+pub fn setup_paused(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    action_map: Res<ActionMap>,
+) {
+    let value = format!(
+        "PAUSED\n\n\n{}  resume\n\n{}  retry level\n\nENTER  restart from level 1\n\nQ  quit to title",
+        action_map.label(Action::Pause),
+        action_map.label(Action::Reset)
+    );
+    spawn_screen_text(&mut commands, &asset_server, value, center(&arena));
+}
+
+/// Leaving the paused screen for anything but resuming unwinds to `Playing`
+/// first, so the level is unloaded and, on a retry, loaded again from scratch.
+/// Return and Q can not be bound, so they never clash with an action.
+pub fn paused(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    action_map: Res<ActionMap>,
+    mut state: ResMut<State<AppState>>,
+    mut level_info: ResMut<LevelInfo>,
+) {
+    if action_map.just_pressed(&keyboard_input, Action::Pause) {
+        action_map.reset(&mut keyboard_input, Action::Pause);
+        state.pop().unwrap();
+    } else if action_map.just_pressed(&keyboard_input, Action::Reset) {
+        action_map.reset(&mut keyboard_input, Action::Reset);
+        state.replace(AppState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.reset(KeyCode::Return);
+        level_info.current_level = 0;
+        state.replace(AppState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::Q) {
+        keyboard_input.reset(KeyCode::Q);
+        state.replace(AppState::Title).unwrap();
+    }
+}
+
+fn score_summary(level_info: &LevelInfo) -> String {
+    match level_info.score {
+        Some(score) => format!(
+            "{}\n\nturns {}  runs {}  walls {}\n\nscore {}  par {}",
+            stars(score.stars(level_info.par)),
+            score.turns,
+            score.runs,
+            score.walls,
+            score.points(),
+            level_info.par
+        ),
+        None => String::new(),
+    }
+}
+
+pub fn setup_level_complete(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    level_info: Res<LevelInfo>,
+) {
+    let value = format!(
+        "LEVEL COMPLETE\n\n{}\n\n\nENTER  next level\n\nESC  title",
+        score_summary(&level_info)
+    );
+    spawn_screen_text(&mut commands, &asset_server, value, center(&arena));
+}
+
+pub fn level_complete(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+    mut level_info: ResMut<LevelInfo>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.reset(KeyCode::Return);
+        level_info.current_level += 1;
+        state.set(AppState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(AppState::Title).unwrap();
+    }
+}
+
+pub fn setup_game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    action_map: Res<ActionMap>,
+) {
+    let value = format!(
+        "GAME OVER\n\nthe runs left can not all\nreach a target any more\n\n\n{}  undo last run\n\n{}  retry level\n\nENTER  restart from level 1\n\nQ  quit to title",
+        action_map.label(Action::Undo),
+        action_map.label(Action::Reset)
+    );
+    spawn_screen_text(&mut commands, &asset_server, value, center(&arena));
+}
+
+/// Undoing takes back the run that left the level unsolvable and goes on
+/// playing. Anything else unwinds to `Playing` first, so the level is
+/// unloaded and, on a retry, loaded again from scratch with all runs and
+/// turns back.
+pub fn game_over(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    action_map: Res<ActionMap>,
+    mut state: ResMut<State<AppState>>,
+    mut level_info: ResMut<LevelInfo>,
+    mut undo_writer: EventWriter<UndoEvent>,
+) {
+    if action_map.just_pressed(&keyboard_input, Action::Undo) {
+        action_map.reset(&mut keyboard_input, Action::Undo);
+        undo_writer.send(UndoEvent {});
+        state.pop().unwrap();
+    } else if action_map.just_pressed(&keyboard_input, Action::Reset) {
+        action_map.reset(&mut keyboard_input, Action::Reset);
+        state.replace(AppState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.reset(KeyCode::Return);
+        level_info.current_level = 0;
+        state.replace(AppState::Playing).unwrap();
+    } else if keyboard_input.just_pressed(KeyCode::Q) {
+        keyboard_input.reset(KeyCode::Q);
+        state.replace(AppState::Title).unwrap();
+    }
+}
+
+pub fn setup_victory(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    level_assets: Res<LevelAssets>,
+    manifests: Res<Assets<LevelManifest>>,
+    levels: Res<Assets<LevelData>>,
+    mut arena: ResMut<Arena>,
+    mut grid: ResMut<Grid>,
+) {
+    let you_won = manifests
+        .get(&level_assets.manifest)
+        .and_then(|manifest| levels.get(&manifest.you_won));
+    if let Some(level_data) = you_won {
+        let board = Board::from_level(&level_data.level);
+        *arena = Arena {
+            width: board.width(),
+            height: board.height(),
+        };
+        spawn_board(&mut commands, &mut materials, &asset_server, &board);
+        grid.0 = board;
+    }
+
+    spawn_screen_text(
+        &mut commands,
+        &asset_server,
+        "ENTER  title".to_string(),
+        Position {
+            x: arena.width as i32 / 2,
+            y: 1,
+        },
+    );
+}
+
+/// Leaves the victory screen.
+pub fn back_to_title(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        keyboard_input.reset(KeyCode::Return);
+        state.set(AppState::Title).unwrap();
+    }
+}
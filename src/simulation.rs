@@ -0,0 +1,234 @@
+use crate::board::{Board, Direction, KeyColor, Position, Tile};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Event {
+    /// The rocket moved onto this tile.
+    Moved(Position),
+    /// The rocket turned on this tile and left a wall behind.
+    CornerWall(Position),
+    /// The rocket ran into the cracked wall on this tile.
+    WallHit(Position),
+    /// The rocket picked up the key on this tile.
+    KeyCollected(Position),
+    /// The rocket entered a teleporter and came out on this tile.
+    Teleported(Position),
+    /// The rocket reached a target, the run is complete.
+    ReachedTarget(Position),
+}
+
+/// A single run of the rocket: it starts standing still, every change of
+/// direction costs a turn once the rocket moves on in the new direction and
+/// every corner it takes turns into a wall, except on the start tile every
+/// run leaves from.
+/// Deflectors turn the rocket for free and keep no wall. Keys only open doors
+/// during the run that picked them up.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    start: Position,
+    max_turns: u32,
+    position: Position,
+    direction: Direction,
+    last_move: Direction,
+    /// The direction the rocket travels in since it last paid for a turn,
+    /// which deflectors change for free.
+    heading: Direction,
+    turns_left: u32,
+    /// Ticks since the rocket launched, including the ones it was stuck.
+    ticks: u32,
+    path: Vec<Position>,
+    corner_walls: Vec<Position>,
+    /// The tile the rocket ran into since it last moved. Pushing on does
+    /// not hit a cracked wall again, not even after steering away and back.
+    bumped: Option<Position>,
+    hits: Vec<Position>,
+    keys: Vec<KeyColor>,
+}
+
+impl Simulation {
+    pub fn new(start: Position, max_turns: u32) -> Self {
+        Simulation {
+            start,
+            max_turns,
+            position: start,
+            direction: Direction::StandStill,
+            last_move: Direction::StandStill,
+            heading: Direction::StandStill,
+            turns_left: max_turns,
+            ticks: 0,
+            path: vec![start],
+            corner_walls: vec![],
+            bumped: None,
+            hits: vec![],
+            keys: vec![],
+        }
+    }
+
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    pub fn max_turns(&self) -> u32 {
+        self.max_turns
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The direction the rocket last moved in, which is where it came from
+    /// when it turns.
+    pub fn last_move(&self) -> Direction {
+        self.last_move
+    }
+
+    /// The direction the rocket travels in without paying for a turn.
+    pub fn heading(&self) -> Direction {
+        self.heading
+    }
+
+    pub fn turns_left(&self) -> u32 {
+        self.turns_left
+    }
+
+    /// Ticks since the rocket launched, including the ones it was stuck.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// The tiles the rocket moved through during the current run.
+    pub fn path(&self) -> &[Position] {
+        &self.path
+    }
+
+    /// The walls left behind during the current run.
+    pub fn corner_walls(&self) -> &[Position] {
+        &self.corner_walls
+    }
+
+    /// The tile the rocket ran into since it last moved.
+    pub fn bumped(&self) -> Option<Position> {
+        self.bumped
+    }
+
+    /// The cracked walls hit during the current run, oldest first.
+    pub fn hits(&self) -> &[Position] {
+        &self.hits
+    }
+
+    /// The keys picked up during the current run, in the order they were
+    /// picked up.
+    pub fn keys(&self) -> &[KeyColor] {
+        &self.keys
+    }
+
+    /// Points the rocket in a new direction. The turn is only paid for once
+    /// the rocket moves on in it, so steering into a wall costs nothing.
+    /// The rocket can not reverse and can not turn once it ran out of turns.
+    pub fn steer(&mut self, direction: Direction) -> bool {
+        if (self.turns_left == 0 && direction != self.heading)
+            || direction == Direction::StandStill
+            || direction == self.direction
+            || direction == self.direction.opposite()
+        {
+            return false;
+        }
+        self.direction = direction;
+        true
+    }
+
+    /// Moves the rocket one tile, unless a wall, gate or locked door is in the
+    /// way. Turning on an empty tile other than the start leaves a wall behind
+    /// once the rocket moved on.
+    pub fn step(&mut self, board: &mut Board) -> Vec<Event> {
+        let mut events = vec![];
+        if self.direction == Direction::StandStill {
+            return events;
+        }
+        self.ticks += 1;
+
+        let next = self.position.step(self.direction);
+        let tile = board.tile(next);
+        let unlocked = matches!(tile, Tile::Door(color) if self.keys.contains(&color));
+        if !unlocked && !board.can_enter(next, self.direction) {
+            if self.bumped != Some(next) && board.hit(next) {
+                self.hits.push(next);
+                events.push(Event::WallHit(next));
+            }
+            self.bumped = Some(next);
+            return events;
+        }
+        self.bumped = None;
+        if self.direction != self.heading {
+            self.turns_left -= 1;
+        }
+
+        let corner = self.position;
+        let turned = self.last_move != Direction::StandStill
+            && self.last_move != self.direction
+            && self.last_move != self.direction.opposite();
+        self.position = next;
+        self.last_move = self.direction;
+        self.path.push(next);
+        events.push(Event::Moved(next));
+
+        if turned && corner != self.start && board.tile(corner) == Tile::Empty {
+            board.set_tile(corner, Tile::Wall);
+            self.corner_walls.push(corner);
+            events.push(Event::CornerWall(corner));
+        }
+
+        if let Tile::Key(color) = tile {
+            if !self.keys.contains(&color) {
+                self.keys.push(color);
+                events.push(Event::KeyCollected(next));
+            }
+        }
+
+        if let Tile::Deflector(deflector) = tile {
+            self.direction = deflector.deflect(self.direction);
+        }
+        self.heading = self.direction;
+
+        if let Some(exit) = board.partner(next) {
+            self.position = exit;
+            self.path.push(exit);
+            events.push(Event::Teleported(exit));
+        }
+
+        if tile == Tile::Target {
+            events.push(Event::ReachedTarget(next));
+        }
+        events
+    }
+
+    /// Starts the next run. Walls left behind by earlier runs stay.
+    pub fn restart(&mut self) {
+        self.position = self.start;
+        self.direction = Direction::StandStill;
+        self.last_move = Direction::StandStill;
+        self.heading = Direction::StandStill;
+        self.turns_left = self.max_turns;
+        self.ticks = 0;
+        self.path = vec![self.start];
+        self.corner_walls.clear();
+        self.bumped = None;
+        self.hits.clear();
+        self.keys.clear();
+    }
+
+    /// Abandons the current run, removing the walls it left behind and
+    /// repairing the cracked walls it hit.
+    pub fn reset(&mut self, board: &mut Board) {
+        for &wall in &self.corner_walls {
+            board.set_tile(wall, Tile::Empty);
+        }
+        for &hit in self.hits.iter().rev() {
+            board.repair(hit);
+        }
+        self.restart();
+    }
+}
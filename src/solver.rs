@@ -0,0 +1,618 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use petgraph::algo::dijkstra;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::{EdgeRef, Reversed};
+use petgraph::Incoming;
+
+use crate::board::{Board, Direction, KeyColor, Position, Tile};
+use crate::simulation::{Event, Simulation};
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Left,
+    Direction::Up,
+    Direction::Right,
+    Direction::Down,
+];
+
+/// Steer the rocket into `direction` while it is on `position`, after
+/// `tick` ticks of the run.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Move {
+    pub position: Position,
+    pub direction: Direction,
+    pub tick: u32,
+}
+
+/// Position, direction, the direction of the last move of a run, the
+/// direction it is paid up for, the tile it ran into, the cracked walls it
+/// hit and the keys it holds.
+type StateKey = (
+    Position,
+    Direction,
+    Direction,
+    Direction,
+    Option<Position>,
+    Vec<Position>,
+    Vec<KeyColor>,
+);
+
+/// A search state: the run so far and the moves that led to it.
+#[derive(Clone)]
+struct Node {
+    simulation: Simulation,
+    board: Board,
+    moves: Vec<Move>,
+    ticks: u32,
+    reached_target: bool,
+}
+
+/// What a [`Planner`] found out about the runs left in a level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outlook {
+    /// The moves of every run left, in order.
+    Solvable(Vec<Vec<Move>>),
+    /// The runs left can not all reach a target any more.
+    Unsolvable,
+    /// The search did not find out yet.
+    Unknown,
+}
+
+/// Finds the run that reaches a target with the fewest turns, and among
+/// those the fewest ticks, or `None` if no run of at most `max_turns` turns
+/// can reach one.
+///
+/// Every tick the rocket may keep its direction or steer once, then moves one
+/// tile. Standing still never helps, since the board does not change while
+/// the rocket waits, but steering against a wall does: it is the only way to
+/// reverse in a dead end, and only the turn the rocket leaves in is paid for.
+/// Running into a cracked wall counts as a change.
+pub fn solve(board: &Board, start: Position, max_turns: u32) -> Option<Vec<Move>> {
+    solve_from(board, &Simulation::new(start, max_turns))
+}
+
+/// Like [`solve`], but continues a run that is already underway on `board`,
+/// which has to contain the walls the run left behind so far.
+pub fn solve_from(board: &Board, simulation: &Simulation) -> Option<Vec<Move>> {
+    let mut budget = u32::MAX;
+    Search::new(board, simulation, false)
+        .next(&mut budget)
+        .map(|node| node.moves)
+}
+
+/// Finds `runs` runs in a row that all reach a target despite the walls the
+/// ones before them leave behind, starting with the run underway in
+/// `simulation`. Each run takes the fewest turns that still let the runs
+/// after it through.
+///
+/// Runs leaving a superset of the walls of a run that did not work out are
+/// not tried, see [`solve`] for why walls only ever block.
+pub fn solve_runs(board: &Board, simulation: &Simulation, runs: u32) -> Outlook {
+    Planner::new(board, simulation, runs).resume(u32::MAX)
+}
+
+/// The search of [`solve_runs`], which can be taken up a few states at a
+/// time so it never holds up a frame for long.
+pub struct Planner {
+    runs: u32,
+    /// A search for every run tried so far, the current one last.
+    plans: Vec<Plan>,
+    /// Boards known to not let the given number of runs through.
+    stuck: HashSet<(Board, u32)>,
+    outlook: Option<Outlook>,
+}
+
+/// The search for one of the runs of a [`Planner`].
+struct Plan {
+    search: Search,
+    /// The cracked walls hit and the walls left by the runs that were tried.
+    tried: Vec<(Vec<Position>, Vec<Position>)>,
+    /// The run being tried, with the board it left behind.
+    run: Option<(Vec<Move>, Board)>,
+}
+
+impl Planner {
+    pub fn new(board: &Board, simulation: &Simulation, runs: u32) -> Self {
+        let mut planner = Planner {
+            runs,
+            plans: vec![],
+            stuck: HashSet::new(),
+            outlook: None,
+        };
+        if runs == 0 {
+            planner.outlook = Some(Outlook::Solvable(vec![]));
+        } else {
+            planner.plans.push(Plan {
+                search: Search::new(board, simulation, runs > 1),
+                tried: vec![],
+                run: None,
+            });
+        }
+        planner
+    }
+
+    /// Expands up to `budget` more search states, returning
+    /// [`Outlook::Unknown`] if that was not enough to find out.
+    pub fn resume(&mut self, mut budget: u32) -> Outlook {
+        if let Some(outlook) = &self.outlook {
+            return outlook.clone();
+        }
+        loop {
+            let runs = self.runs - self.plans.len() as u32 + 1;
+            let plan = match self.plans.last_mut() {
+                Some(plan) => plan,
+                None => break,
+            };
+            let node = match plan.search.next(&mut budget) {
+                Some(node) => node,
+                None if budget == 0 => return Outlook::Unknown,
+                None => {
+                    self.plans.pop();
+                    if let Some((_, board)) = self.plans.last_mut().and_then(|plan| plan.run.take())
+                    {
+                        self.stuck.insert((board, runs));
+                    }
+                    continue;
+                }
+            };
+
+            let walls = sorted(node.simulation.corner_walls());
+            let hits = sorted(node.simulation.hits());
+            if plan.tried.iter().any(|(tried_hits, tried_walls)| {
+                *tried_hits == hits && is_subset(tried_walls, &walls)
+            }) {
+                continue;
+            }
+            plan.tried.push((hits, walls));
+
+            if runs == 1 {
+                let mut moves: Vec<Vec<Move>> = self
+                    .plans
+                    .iter_mut()
+                    .filter_map(|plan| plan.run.take())
+                    .map(|(moves, _)| moves)
+                    .collect();
+                moves.push(node.moves);
+                self.plans.clear();
+                let outlook = Outlook::Solvable(moves);
+                self.outlook = Some(outlook.clone());
+                return outlook;
+            }
+            let key = (node.board, runs - 1);
+            if self.stuck.contains(&key) {
+                continue;
+            }
+            let mut next = node.simulation;
+            next.restart();
+            let search = Search::new(&key.0, &next, runs > 2);
+            plan.run = Some((node.moves, key.0));
+            self.plans.push(Plan {
+                search,
+                tried: vec![],
+                run: None,
+            });
+        }
+        self.outlook = Some(Outlook::Unsolvable);
+        Outlook::Unsolvable
+    }
+}
+
+/// A search for the runs that reach a target, cheapest first.
+struct Search {
+    max_turns: u32,
+    estimates: Estimates,
+    /// The way of the runs after this one, if they still have to reach a
+    /// target, so runs leaving walls that cut them off can be dropped.
+    way: Option<Way>,
+    nodes: Vec<Node>,
+    queue: BinaryHeap<Reverse<(u32, u32, usize)>>,
+    // States that were expanded, by position and direction: the turns left
+    // and the walls spawned on the way there.
+    expanded: HashMap<StateKey, Vec<(u32, Vec<Position>)>>,
+}
+
+impl Search {
+    fn new(board: &Board, simulation: &Simulation, runs_after: bool) -> Self {
+        let max_turns = simulation.max_turns();
+        let estimates = Estimates::new(board);
+        let way = if runs_after {
+            Some(Way::new(&estimates, simulation))
+        } else {
+            None
+        };
+        // Nothing this run does can open up a way that is cut off already.
+        let cut_off = matches!(&way, Some(way) if !way.tiles.contains(&true));
+        let mut queue = BinaryHeap::new();
+        match estimates.turns_left(simulation) {
+            Some(turns) if turns <= simulation.turns_left() && !cut_off => {
+                let turns_used = max_turns - simulation.turns_left();
+                queue.push(Reverse((turns_used + turns, 0, 0)));
+            }
+            _ => {}
+        }
+        Search {
+            max_turns,
+            estimates,
+            way,
+            nodes: vec![Node {
+                simulation: simulation.clone(),
+                board: board.clone(),
+                moves: vec![],
+                ticks: 0,
+                reached_target: false,
+            }],
+            queue,
+            expanded: HashMap::new(),
+        }
+    }
+
+    /// The next run that reaches a target, or `None` once there are no more
+    /// or `budget` ran out. Every expanded state takes one off the budget.
+    fn next(&mut self, budget: &mut u32) -> Option<Node> {
+        while let Some(&Reverse((_, _, index))) = self.queue.peek() {
+            let node = &self.nodes[index];
+            if node.reached_target {
+                self.queue.pop();
+                return Some(node.clone());
+            }
+            if *budget == 0 {
+                return None;
+            }
+            self.queue.pop();
+            *budget -= 1;
+            let simulation = &node.simulation;
+
+            // Walls only ever block the rocket, so a state is as good as
+            // another one that has no more turns left and a superset of its
+            // walls.
+            let walls = sorted(simulation.corner_walls());
+            // Breaking a cracked wall can open a way as well as take away a
+            // place to stop, so only states with the same hits are compared.
+            let hits = sorted(simulation.hits());
+            let mut keys = simulation.keys().to_vec();
+            keys.sort();
+            let key = (
+                simulation.position(),
+                simulation.direction(),
+                simulation.last_move(),
+                simulation.heading(),
+                simulation.bumped(),
+                hits,
+                keys,
+            );
+            let seen = self.expanded.entry(key).or_default();
+            if seen.iter().any(|(turns_left, seen_walls)| {
+                *turns_left >= simulation.turns_left() && is_subset(seen_walls, &walls)
+            }) {
+                continue;
+            }
+            seen.push((simulation.turns_left(), walls));
+
+            let mut children = vec![];
+            for steering in steerings(simulation.direction()) {
+                let mut simulation = node.simulation.clone();
+                let mut board = node.board.clone();
+                let mut moves = node.moves.clone();
+                let position = simulation.position();
+                if let Some(direction) = steering {
+                    if !simulation.steer(direction) {
+                        continue;
+                    }
+                    moves.push(Move {
+                        position,
+                        direction,
+                        tick: simulation.ticks(),
+                    });
+                }
+
+                let events = simulation.step(&mut board);
+                let reached_target = events
+                    .iter()
+                    .any(|event| matches!(event, Event::ReachedTarget(_)));
+                let hit = events
+                    .iter()
+                    .any(|event| matches!(event, Event::WallHit(_)));
+                if steering.is_none() && simulation.position() == position && !hit {
+                    continue;
+                }
+                let walled = events
+                    .iter()
+                    .any(|event| matches!(event, Event::CornerWall(_)));
+                if let Some(way) = self.way.as_mut().filter(|_| walled) {
+                    if !way.is_open(&self.estimates, &simulation) {
+                        continue;
+                    }
+                }
+
+                children.push(Node {
+                    simulation,
+                    board,
+                    moves,
+                    ticks: node.ticks + 1,
+                    reached_target,
+                });
+            }
+            for child in children {
+                // Runs that can not make it with the turns they have left
+                // are dropped right away.
+                let turns = if child.reached_target {
+                    0
+                } else {
+                    match self.estimates.turns_left(&child.simulation) {
+                        Some(turns) if turns <= child.simulation.turns_left() => turns,
+                        _ => continue,
+                    }
+                };
+                let turns_used = self.max_turns - child.simulation.turns_left();
+                self.queue
+                    .push(Reverse((turns_used + turns, child.ticks, self.nodes.len())));
+                self.nodes.push(child);
+            }
+        }
+        None
+    }
+}
+
+/// The fewest turns a run needs to reach a target from every tile and
+/// direction, not counting the walls it leaves behind and counting cracked
+/// walls as broken and doors as open. A search for the runs that reach a
+/// target expands them in the order of their turns plus this estimate, which
+/// never overestimates, so the cheapest runs still come first.
+struct Estimates {
+    width: u32,
+    /// The moves between states, pointing backwards.
+    graph: DiGraph<(), u32>,
+    turns: Vec<Option<u32>>,
+}
+
+impl Estimates {
+    fn new(board: &Board) -> Self {
+        let width = board.width();
+        let states = (board.width() * board.height()) as usize * DIRECTIONS.len();
+        let node = |pos: Position, direction: Direction| {
+            NodeIndex::new(state(width, pos, direction).expect("a direction to move in"))
+        };
+
+        // The edges point backwards, from where the rocket ends up to where
+        // it came from, and the last node stands for any target.
+        let mut g = DiGraph::<(), u32>::with_capacity(states + 1, 0);
+        for _ in 0..=states {
+            g.add_node(());
+        }
+        for (here, tile) in board.tiles() {
+            if tile == Tile::Wall {
+                continue;
+            }
+            for &direction in &DIRECTIONS {
+                for &other in DIRECTIONS.iter().filter(|&&other| other != direction) {
+                    g.add_edge(node(here, other), node(here, direction), 1);
+                }
+
+                let next = here.step(direction);
+                let opens = matches!(board.tile(next), Tile::Cracked(_) | Tile::Door(_));
+                if !opens && !board.can_enter(next, direction) {
+                    continue;
+                }
+                let there = match board.tile(next) {
+                    Tile::Target => NodeIndex::new(states),
+                    Tile::Deflector(deflector) => node(next, deflector.deflect(direction)),
+                    _ => node(board.partner(next).unwrap_or(next), direction),
+                };
+                g.add_edge(there, node(here, direction), 0);
+            }
+        }
+
+        let turns_map = dijkstra(&g, NodeIndex::new(states), None, |edge| *edge.weight());
+        let turns = (0..states)
+            .map(|index| turns_map.get(&NodeIndex::new(index)).copied())
+            .collect();
+        Estimates {
+            width,
+            graph: g,
+            turns,
+        }
+    }
+
+    /// The fewest turns `simulation` needs to reach a target, `None` if it
+    /// can not reach one at all.
+    fn turns_left(&self, simulation: &Simulation) -> Option<u32> {
+        let heading = simulation.heading();
+        DIRECTIONS
+            .iter()
+            .filter_map(|&direction| {
+                let turns = self.turns[state(self.width, simulation.position(), direction)?]?;
+                Some(turns + (direction != heading) as u32)
+            })
+            .min()
+    }
+
+    /// The tiles, by index, `simulation` can pass on its way to a target
+    /// within the turns it has left.
+    fn way(&self, simulation: &Simulation) -> Vec<bool> {
+        let mut tiles = vec![false; self.turns.len() / DIRECTIONS.len()];
+        for &direction in &DIRECTIONS {
+            let from = match state(self.width, simulation.position(), direction) {
+                Some(index) => NodeIndex::new(index),
+                None => continue,
+            };
+            let steered = (direction != simulation.heading()) as u32;
+            let spent = dijkstra(Reversed(&self.graph), from, None, |edge| *edge.weight());
+            for (node, turns) in spent {
+                let left = self.turns.get(node.index()).copied().flatten();
+                if matches!(left, Some(left) if steered + turns + left <= simulation.turns_left()) {
+                    tiles[node.index() / DIRECTIONS.len()] = true;
+                }
+            }
+        }
+        tiles
+    }
+}
+
+/// Whether the walls a run leaves behind still let a new run reach a target
+/// within its turns, as far as [`Estimates`] can tell. Walls only ever take
+/// that away, so once a run cuts off the runs after it, taking it any further
+/// is useless.
+struct Way {
+    width: u32,
+    start: Position,
+    max_turns: u32,
+    /// The tiles, by index, a new run can pass on its way to a target
+    /// within its turns. Walls anywhere else leave the way open.
+    tiles: Vec<bool>,
+    /// The tiles of ways that were found, sorted. Walls elsewhere leave
+    /// them open.
+    open: Vec<Vec<usize>>,
+    /// Walls that were found to cut off the way, sorted. More walls do too.
+    cut: Vec<Vec<usize>>,
+}
+
+impl Way {
+    fn new(estimates: &Estimates, simulation: &Simulation) -> Self {
+        let next = Simulation::new(simulation.start(), simulation.max_turns());
+        Way {
+            width: estimates.width,
+            start: next.start(),
+            max_turns: next.max_turns(),
+            tiles: estimates.way(&next),
+            open: vec![],
+            cut: vec![],
+        }
+    }
+
+    /// Whether a new run still gets through once `simulation` left its
+    /// walls on the board the run started on.
+    fn is_open(&mut self, estimates: &Estimates, simulation: &Simulation) -> bool {
+        let mut walls: Vec<usize> = simulation
+            .corner_walls()
+            .iter()
+            .map(|&pos| tile(self.width, pos))
+            .filter(|&index| self.tiles[index])
+            .collect();
+        walls.sort_unstable();
+        let blocks = |walls: &[usize], way: &[usize]| {
+            walls.iter().any(|wall| way.binary_search(wall).is_ok())
+        };
+        if self.open.iter().any(|way| !blocks(&walls, way)) {
+            return true;
+        }
+        if self.cut.iter().any(|cut| is_subset(cut, &walls)) {
+            return false;
+        }
+
+        if let Some(way) = self.find(estimates, &walls) {
+            self.open.push(way);
+            return true;
+        }
+        // Fewer walls likely cut it off already, which then rules out a lot
+        // more runs.
+        let mut cut = walls;
+        let mut index = 0;
+        while index < cut.len() {
+            let wall = cut.remove(index);
+            if self.find(estimates, &cut).is_some() {
+                cut.insert(index, wall);
+                index += 1;
+            }
+        }
+        self.cut.push(cut);
+        false
+    }
+
+    /// The tiles of a way a new run can take to a target if there were
+    /// `walls` on the board the run started on, sorted.
+    fn find(&self, estimates: &Estimates, walls: &[usize]) -> Option<Vec<usize>> {
+        let graph = &estimates.graph;
+        let target = NodeIndex::new(estimates.turns.len());
+        let mut turns = vec![None; graph.node_count()];
+        let mut came_from = vec![None; graph.node_count()];
+        // Moves cost nothing and turns one, so the states are visited in
+        // the order of their turns by putting moves first in line.
+        let mut queue = VecDeque::new();
+        for &launch in &DIRECTIONS {
+            if let Some(index) = state(self.width, self.start, launch) {
+                // Launching costs a turn like every other one.
+                turns[index] = Some(1);
+                queue.push_back(NodeIndex::new(index));
+            }
+        }
+        while let Some(node) = queue.pop_front() {
+            let spent = turns[node.index()].expect("queued states to have turns");
+            // The edges point backwards, from where the rocket ends up.
+            for edge in graph.edges_directed(node, Incoming) {
+                let next = edge.source();
+                let cost = spent + edge.weight();
+                let walled = walls
+                    .binary_search(&(next.index() / DIRECTIONS.len()))
+                    .is_ok();
+                if walled
+                    || cost > self.max_turns
+                    || matches!(turns[next.index()], Some(turns) if turns <= cost)
+                {
+                    continue;
+                }
+                turns[next.index()] = Some(cost);
+                came_from[next.index()] = Some(node);
+                if next == target {
+                    let mut way = vec![];
+                    let mut node = node;
+                    loop {
+                        way.push(node.index() / DIRECTIONS.len());
+                        match came_from[node.index()] {
+                            Some(before) => node = before,
+                            None => break,
+                        }
+                    }
+                    way.sort_unstable();
+                    way.dedup();
+                    return Some(way);
+                }
+                if *edge.weight() == 0 {
+                    queue.push_front(next);
+                } else {
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The index of `pos`.
+fn tile(width: u32, pos: Position) -> usize {
+    (pos.x as u32 + pos.y as u32 * width) as usize
+}
+
+/// The index of the rocket on `pos` moving in `direction`.
+fn state(width: u32, pos: Position, direction: Direction) -> Option<usize> {
+    let direction = DIRECTIONS.iter().position(|&d| d == direction)?;
+    Some(tile(width, pos) * DIRECTIONS.len() + direction)
+}
+
+/// The ways to steer during a single tick: keep going or turn to either
+/// side. A rocket standing still can pick any direction.
+fn steerings(direction: Direction) -> Vec<Option<Direction>> {
+    if direction == Direction::StandStill {
+        return DIRECTIONS.iter().map(|&d| Some(d)).collect();
+    }
+    let mut steerings = vec![None];
+    steerings.extend(
+        DIRECTIONS
+            .iter()
+            .filter(|&&d| d != direction && d != direction.opposite())
+            .map(|&d| Some(d)),
+    );
+    steerings
+}
+
+fn sorted(positions: &[Position]) -> Vec<Position> {
+    let mut positions = positions.to_vec();
+    positions.sort_by_key(|pos| (pos.x, pos.y));
+    positions
+}
+
+/// Both slices are sorted.
+fn is_subset<T: PartialEq>(small: &[T], large: &[T]) -> bool {
+    let mut large = large.iter();
+    small.iter().all(|pos| large.any(|other| other == pos))
+}
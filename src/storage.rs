@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use butterfly_effect::SaveData;
+
+/// Reads the save data, starting over with defaults if there is none or it
+/// can not be read.
+pub fn load() -> SaveData {
+    match read() {
+        Ok(Some(source)) => SaveData::parse(&source).unwrap_or_else(|e| {
+            warn!("ignoring save data: {}", e);
+            SaveData::default()
+        }),
+        Ok(None) => SaveData::default(),
+        Err(e) => {
+            warn!("could not read save data: {}", e);
+            SaveData::default()
+        }
+    }
+}
+
+pub fn store(save: &SaveData) {
+    if let Err(e) = save.to_ron().and_then(|source| write(&source)) {
+        warn!("could not write save data: {}", e);
+    }
+}
+
+#[cfg(not(feature = "web"))]
+fn save_path() -> anyhow::Result<std::path::PathBuf> {
+    use std::env;
+    use std::path::PathBuf;
+
+    let config_dir = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    let config_dir = config_dir.ok_or_else(|| anyhow::anyhow!("no config directory"))?;
+    Ok(config_dir.join("butterfly-effect").join("save.ron"))
+}
+
+#[cfg(not(feature = "web"))]
+fn read() -> anyhow::Result<Option<String>> {
+    match std::fs::read_to_string(save_path()?) {
+        Ok(source) => Ok(Some(source)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(not(feature = "web"))]
+fn write(source: &str) -> anyhow::Result<()> {
+    let path = save_path()?;
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    std::fs::write(path, source)?;
+    Ok(())
+}
+
+#[cfg(feature = "web")]
+const STORAGE_KEY: &str = "butterfly-effect-save";
+
+#[cfg(feature = "web")]
+fn local_storage() -> anyhow::Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| anyhow::anyhow!("localStorage is not available"))
+}
+
+#[cfg(feature = "web")]
+fn read() -> anyhow::Result<Option<String>> {
+    local_storage()?
+        .get_item(STORAGE_KEY)
+        .map_err(|_| anyhow::anyhow!("could not read from localStorage"))
+}
+
+#[cfg(feature = "web")]
+fn write(source: &str) -> anyhow::Result<()> {
+    local_storage()?
+        .set_item(STORAGE_KEY, source)
+        .map_err(|_| anyhow::anyhow!("could not write to localStorage"))
+}
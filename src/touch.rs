@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+
+use butterfly_effect::{Action, Direction};
+
+use crate::{InputBuffer, LevelInfo, ResetEvent};
+
+/// How far in pixels the pointer has to travel to count as a swipe instead
+/// of a tap.
+const SWIPE_DISTANCE: f32 = 30.0;
+
+/// Makes a scoreboard sprite act as an on-screen button for an action when
+/// tapped.
+pub struct ControlButton(pub Action);
+
+/// Where the left mouse button went down, while it is held.
+#[derive(Default)]
+pub struct PointerGesture {
+    start: Option<Vec2>,
+}
+
+/// A tap or click, at the coordinates sprites are drawn at, which have their
+/// origin in the middle of the window and y pointing up.
+pub struct Tap(pub Vec2);
+
+/// The direction of the longer axis of a swipe.
+fn swipe_direction(delta: Vec2) -> Direction {
+    if delta.x.abs() > delta.y.abs() {
+        if delta.x > 0.0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    } else if delta.y > 0.0 {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
+
+/// Swiping steers the rocket like the direction keys, anything shorter is a
+/// [`Tap`].
+///
+/// Gestures are read from the left mouse button and the cursor, since
+/// browsers hand touches to winit as pointer events, which arrive as mouse
+/// input. Dragging the mouse on the desktop swipes the same way.
+pub fn pointer_input(
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    level_info: Res<LevelInfo>,
+    mut gesture: ResMut<PointerGesture>,
+    mut input_buffer: ResMut<InputBuffer>,
+    mut tap_writer: EventWriter<Tap>,
+) {
+    let window = windows.get_primary().unwrap();
+    if mouse_input.just_pressed(MouseButton::Left) {
+        gesture.start = window.cursor_position();
+    }
+    if !mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    let (start, end) = match (gesture.start.take(), window.cursor_position()) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return,
+    };
+    if !level_info.loaded {
+        return;
+    }
+
+    // The cursor is measured from the bottom left corner with y pointing
+    // up, on the web as well.
+    let delta = end - start;
+    if delta.length() >= SWIPE_DISTANCE {
+        input_buffer.push(swipe_direction(delta));
+    } else {
+        tap_writer.send(Tap(end - Vec2::new(window.width(), window.height()) / 2.0));
+    }
+}
+
+/// Tapping one of the control sprites on the scoreboard presses it, unless
+/// it is hidden.
+pub fn control_buttons(
+    mut reader: EventReader<Tap>,
+    mut input_buffer: ResMut<InputBuffer>,
+    mut reset_writer: EventWriter<ResetEvent>,
+    buttons: Query<(&ControlButton, &Transform, &Sprite, &Visible)>,
+) {
+    for Tap(tap) in reader.iter() {
+        let pressed = buttons.iter().find(|(_, transform, sprite, visible)| {
+            let offset = *tap - transform.translation.truncate();
+            visible.is_visible
+                && offset.x.abs() <= sprite.size.x / 2.0
+                && offset.y.abs() <= sprite.size.y / 2.0
+        });
+        if let Some((ControlButton(action), _, _, _)) = pressed {
+            if let Some(dir) = action.direction() {
+                input_buffer.push(dir);
+            } else if *action == Action::Reset {
+                reset_writer.send(ResetEvent {});
+            }
+        }
+    }
+}
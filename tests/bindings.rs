@@ -0,0 +1,23 @@
+use butterfly_effect::{Action, Bindings};
+
+#[test]
+fn rebinding_takes_the_key_from_other_actions() {
+    let mut bindings = Bindings::default();
+    bindings.rebind(Action::Hint, "W");
+    assert_eq!(bindings.keys(Action::Hint), &["W"]);
+    assert_eq!(bindings.keys(Action::Up), &["Up"]);
+}
+
+#[test]
+fn rebinding_swaps_the_only_key_of_an_action() {
+    let mut bindings = Bindings::default();
+    bindings.rebind(Action::Hint, "R");
+    assert_eq!(bindings.keys(Action::Hint), &["R"]);
+    assert_eq!(bindings.keys(Action::Reset), &["H"]);
+
+    bindings.rebind(Action::Up, "Escape");
+    assert_eq!(bindings.keys(Action::Pause), &["Up", "W"]);
+    for &action in Action::ALL.iter() {
+        assert!(!bindings.keys(action).is_empty(), "{}", action.name());
+    }
+}
@@ -0,0 +1,369 @@
+use std::fs;
+
+use butterfly_effect::{
+    solve, solve_runs, Board, Direction, Event, KeyColor, Level, Manifest, Move, Outlook, Planner,
+    Position, Score, Simulation, Tile,
+};
+
+fn shipped_levels() -> Vec<(String, Level)> {
+    let manifest = Manifest::parse(&fs::read_to_string("assets/levels/levels.manifest").unwrap())
+        .expect("levels.manifest should parse");
+    manifest
+        .levels
+        .into_iter()
+        .map(|file| {
+            let source = fs::read_to_string(format!("assets/levels/{}", file)).unwrap();
+            let level = Level::parse(&source).unwrap_or_else(|e| panic!("{}: {}", file, e));
+            (file, level)
+        })
+        .collect()
+}
+
+fn wall_in(board: &mut Board, pos: Position) {
+    for &direction in &[
+        Direction::Left,
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+    ] {
+        board.set_tile(pos.step(direction), Tile::Wall);
+    }
+}
+
+#[test]
+fn every_level_has_a_reachable_target() {
+    for (file, level) in shipped_levels() {
+        let board = Board::from_level(&level);
+        assert!(board.target_reachable(board.start()), "{}", file);
+    }
+}
+
+/// Flies `moves` from the start of a new run, returning whether it reached a
+/// target with every move taken on its tick and tile and none left over.
+fn play(board: &mut Board, simulation: &mut Simulation, moves: &[Move]) -> bool {
+    simulation.restart();
+    let mut moves = moves.iter().peekable();
+    // No run on a shipped level is anywhere near this long.
+    for _ in 0..1000 {
+        if let Some(turn) = moves.next_if(|turn| turn.tick == simulation.ticks()) {
+            if turn.position != simulation.position() || !simulation.steer(turn.direction) {
+                return false;
+            }
+        }
+        let events = simulation.step(board);
+        if events
+            .iter()
+            .any(|event| matches!(event, Event::ReachedTarget(_)))
+        {
+            return moves.next().is_none();
+        }
+    }
+    false
+}
+
+/// Flies the runs [`solve_runs`] finds for a whole level, returning the score
+/// they earn.
+fn clear(file: &str, level: &Level) -> Score {
+    let mut board = Board::from_level(level);
+    let mut simulation = Simulation::new(board.start(), level.turns);
+    let runs = match solve_runs(&board, &simulation, level.runs) {
+        Outlook::Solvable(runs) => runs,
+        outlook => panic!("{}: {:?}", file, outlook),
+    };
+    let mut score = Score {
+        turns: 0,
+        runs: level.runs,
+        required_runs: level.runs,
+        walls: 0,
+    };
+    for (run, moves) in runs.iter().enumerate() {
+        assert!(
+            play(&mut board, &mut simulation, moves),
+            "{}: run {}",
+            file,
+            run
+        );
+        score.turns += level.turns - simulation.turns_left();
+        score.walls += simulation.corner_walls().len() as u32;
+    }
+    score
+}
+
+#[test]
+fn every_level_is_solvable_within_its_turns() {
+    for (file, level) in shipped_levels() {
+        clear(&file, &level);
+    }
+}
+
+#[test]
+fn every_par_is_the_score_of_the_solver() {
+    for (file, level) in shipped_levels() {
+        assert_eq!(clear(&file, &level).points(), level.par, "{}", file);
+    }
+}
+
+#[test]
+fn following_hints_clears_every_level() {
+    for (file, level) in shipped_levels() {
+        let mut board = Board::from_level(&level);
+        let mut simulation = Simulation::new(board.start(), level.turns);
+        for runs_left in (1..=level.runs).rev() {
+            let mut hint = None;
+            loop {
+                if hint.is_none() {
+                    match solve_runs(&board, &simulation, runs_left) {
+                        Outlook::Solvable(runs) => hint = runs[0].first().copied(),
+                        outlook => panic!("{}: {} runs left: {:?}", file, runs_left, outlook),
+                    }
+                }
+                if let Some(turn) = hint.filter(|turn| turn.position == simulation.position()) {
+                    assert!(simulation.steer(turn.direction), "{}", file);
+                    hint = None;
+                }
+                let events = simulation.step(&mut board);
+                if events
+                    .iter()
+                    .any(|event| matches!(event, Event::ReachedTarget(_)))
+                {
+                    break;
+                }
+            }
+            simulation.restart();
+        }
+    }
+}
+
+/// Flies a run that steers at random every few ticks, returning whether it
+/// reached a target.
+fn wander(board: &mut Board, simulation: &mut Simulation, seed: &mut u32) -> bool {
+    simulation.restart();
+    for _ in 0..300 {
+        // xorshift, so every run of the test wanders the same way.
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 17;
+        *seed ^= *seed << 5;
+        if simulation.direction() == Direction::StandStill || *seed & 3 == 0 {
+            let directions = [
+                Direction::Left,
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+            ];
+            simulation.steer(directions[(*seed >> 8) as usize % 4]);
+        }
+        let events = simulation.step(board);
+        if events
+            .iter()
+            .any(|event| matches!(event, Event::ReachedTarget(_)))
+        {
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+fn every_level_is_decided_after_a_first_run() {
+    // A few seconds of the states the game searches every frame.
+    const BUDGET: u32 = 100_000;
+    let mut seed = 1;
+    for (file, level) in shipped_levels() {
+        let mut first_runs = vec![];
+        let board = Board::from_level(&level);
+        if let Some(moves) = solve(&board, board.start(), level.turns) {
+            let mut board = board.clone();
+            let mut simulation = Simulation::new(board.start(), level.turns);
+            assert!(play(&mut board, &mut simulation, &moves), "{}", file);
+            first_runs.push(board);
+        }
+        while first_runs.len() < 30 {
+            let mut board = board.clone();
+            let mut simulation = Simulation::new(board.start(), level.turns);
+            if wander(&mut board, &mut simulation, &mut seed) {
+                first_runs.push(board);
+            }
+        }
+
+        for board in first_runs {
+            let simulation = Simulation::new(board.start(), level.turns);
+            let outlook = Planner::new(&board, &simulation, level.runs - 1).resume(BUDGET);
+            assert_ne!(outlook, Outlook::Unknown, "{}", file);
+        }
+    }
+}
+
+#[test]
+fn walled_in_start_reaches_nothing() {
+    for (file, level) in shipped_levels() {
+        let mut board = Board::from_level(&level);
+        let start = board.start();
+        wall_in(&mut board, start);
+        assert!(!board.target_reachable(board.start()), "{}", file);
+        assert!(
+            solve(&board, board.start(), level.turns).is_none(),
+            "{}",
+            file
+        );
+    }
+}
+
+#[test]
+fn walled_in_targets_are_unreachable() {
+    for (file, level) in shipped_levels() {
+        let mut board = Board::from_level(&level);
+        let targets: Vec<Position> = board.targets().collect();
+        for target in targets {
+            wall_in(&mut board, target);
+        }
+        assert!(!board.target_reachable(board.start()), "{}", file);
+    }
+}
+
+#[test]
+fn cells_do_not_alias_on_wide_boards() {
+    // With `x * width + y` numbering (2, 1) and (1, 5) of a 4 wide board
+    // were the same node, connecting the two halves of this board.
+    let mut board = Board::new(4, 6, Position { x: 2, y: 1 });
+    for x in 0..4 {
+        board.set_tile(Position { x, y: 3 }, Tile::Wall);
+    }
+    board.set_tile(Position { x: 1, y: 5 }, Tile::Target);
+    assert!(!board.target_reachable(board.start()));
+
+    board.set_tile(Position { x: 1, y: 3 }, Tile::Empty);
+    assert!(board.target_reachable(board.start()));
+}
+
+#[test]
+fn the_rocket_can_leave_a_wall_it_stands_on() {
+    let level = Level::parse("turns: 1\nruns: 1\n---\nS T").unwrap();
+    let mut board = Board::from_level(&level);
+    let start = board.start();
+    board.set_tile(start, Tile::Wall);
+    assert!(board.target_reachable(start));
+
+    let mut simulation = Simulation::new(start, level.turns);
+    assert!(simulation.steer(Direction::Right));
+    simulation.step(&mut board);
+    assert_ne!(simulation.position(), start);
+}
+
+#[test]
+fn teleporters_carry_the_rocket_across_walls() {
+    let level = Level::parse("turns: 1\nruns: 1\n---\n  W   \nS1W1 T\n  W   ").unwrap();
+    let mut board = Board::from_level(&level);
+    assert!(board.target_reachable(board.start()));
+    assert!(solve(&board, board.start(), level.turns).is_some());
+
+    board.set_tile(Position { x: 4, y: 2 }, Tile::Empty);
+    assert!(!board.target_reachable(board.start()));
+}
+
+#[test]
+fn gates_only_open_one_way() {
+    let level = Level::parse("turns: 1\nruns: 1\n---\nS > T").unwrap();
+    let board = Board::from_level(&level);
+    assert!(board.target_reachable(board.start()));
+    assert!(solve(&board, board.start(), level.turns).is_some());
+
+    let level = Level::parse("turns: 1\nruns: 1\n---\nS < T").unwrap();
+    let board = Board::from_level(&level);
+    assert!(!board.target_reachable(board.start()));
+    assert!(solve(&board, board.start(), level.turns).is_none());
+}
+
+#[test]
+fn deflectors_turn_for_free() {
+    for source in &["  T\nS /", "S \\\n  T"] {
+        let level = Level::parse(&format!("turns: 1\nruns: 1\n---\n{}", source)).unwrap();
+        let mut board = Board::from_level(&level);
+        let mut simulation = Simulation::new(board.start(), level.turns);
+
+        // Launching spends the only turn, the deflector turns towards the
+        // target without another one.
+        assert!(simulation.steer(Direction::Right), "{}", source);
+        simulation.step(&mut board);
+        let turns_left = simulation.turns_left();
+        let mut reached = false;
+        for _ in 0..10 {
+            let events = simulation.step(&mut board);
+            if events
+                .iter()
+                .any(|event| matches!(event, Event::ReachedTarget(_)))
+            {
+                reached = true;
+                break;
+            }
+        }
+        assert!(reached, "{}", source);
+        assert!(simulation.corner_walls().is_empty(), "{}", source);
+        assert_eq!(simulation.turns_left(), turns_left, "{}", source);
+    }
+}
+
+#[test]
+fn steering_into_a_wall_costs_no_turn() {
+    let level = Level::parse("turns: 2\nruns: 1\n---\nSW\n T").unwrap();
+    let mut board = Board::from_level(&level);
+    let mut simulation = Simulation::new(board.start(), level.turns);
+
+    // Up runs into the border, right into the wall next to the start.
+    for &direction in &[Direction::Up, Direction::Right] {
+        assert!(simulation.steer(direction));
+        simulation.step(&mut board);
+        assert_eq!(simulation.position(), board.start());
+        assert_eq!(simulation.turns_left(), 2);
+    }
+
+    assert!(simulation.steer(Direction::Down));
+    simulation.step(&mut board);
+    assert_ne!(simulation.position(), board.start());
+    assert_eq!(simulation.turns_left(), 1);
+}
+
+#[test]
+fn cracked_walls_break_and_are_repaired_on_reset() {
+    let level = Level::parse("turns: 1\nruns: 1\n---\nS x T").unwrap();
+    let board = Board::from_level(&level);
+    assert!(solve(&board, board.start(), level.turns).is_some());
+
+    // Pushing on against a wall does not hit it again.
+    let level = Level::parse("turns: 1\nruns: 1\n---\nS X T").unwrap();
+    let mut board = Board::from_level(&level);
+    assert!(board.target_reachable(board.start()));
+    assert!(solve(&board, board.start(), level.turns).is_none());
+
+    let wall = Position { x: 3, y: 1 };
+    let mut simulation = Simulation::new(board.start(), level.turns);
+    simulation.steer(Direction::Right);
+    for _ in 0..5 {
+        simulation.step(&mut board);
+    }
+    assert_eq!(board.tile(wall), Tile::Cracked(2));
+    simulation.reset(&mut board);
+    assert_eq!(board.tile(wall), Tile::Cracked(3));
+}
+
+#[test]
+fn doors_open_for_the_run_holding_their_key() {
+    let level = Level::parse("turns: 1\nruns: 1\n---\nS g R T").unwrap();
+    let board = Board::from_level(&level);
+    assert!(solve(&board, board.start(), level.turns).is_none());
+
+    let level = Level::parse("turns: 1\nruns: 1\n---\nS r R T").unwrap();
+    let mut board = Board::from_level(&level);
+    assert!(solve(&board, board.start(), level.turns).is_some());
+
+    // Resetting the run drops the key again.
+    let door = Position { x: 5, y: 1 };
+    let mut simulation = Simulation::new(board.start(), level.turns);
+    simulation.steer(Direction::Right);
+    while simulation.position().step(Direction::Right) != door {
+        simulation.step(&mut board);
+    }
+    assert_eq!(simulation.keys(), &[KeyColor::Red]);
+    simulation.reset(&mut board);
+    assert!(simulation.keys().is_empty());
+    assert_eq!(board.tile(door), Tile::Door(KeyColor::Red));
+}
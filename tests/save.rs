@@ -0,0 +1,107 @@
+use butterfly_effect::{Action, BestResult, Bindings, SaveData, Score};
+
+const V1: &str = "(
+    version: 1,
+    progress: (cleared: [0, 1]),
+    best: {
+        0: (turns: 12, runs: 3),
+    },
+    settings: (music: false),
+)";
+
+const V2: &str = "(
+    version: 2,
+    progress: (cleared: [0]),
+    best: {
+        0: (turns: 14, runs: 4, walls: 9, stars: 2),
+    },
+    settings: (music: true),
+)";
+
+const V3: &str = "(
+    version: 3,
+    progress: (cleared: [0]),
+    best: {
+        0: (turns: 14, runs: 4, walls: 9, stars: 2),
+    },
+    settings: (
+        music: true,
+        bindings: {
+            Up: [\"Up\"],
+            Down: [\"Down\"],
+            Left: [\"Left\"],
+            Right: [\"Right\"],
+            Reset: [\"Space\"],
+            Undo: [\"U\"],
+            Hint: [\"H\"],
+            Pause: [\"Escape\"],
+        },
+    ),
+)";
+
+fn best(turns: u32, runs: u32, walls: u32, stars: u32) -> BestResult {
+    BestResult {
+        fewest_turns: turns,
+        fewest_runs: runs,
+        fewest_walls: walls,
+        stars,
+    }
+}
+
+#[test]
+fn save_data_survives_a_round_trip() {
+    let mut save = SaveData::default();
+    save.record_clear(0, best(14, 3, 9, 3));
+    save.record_clear(1, best(30, 5, 20, 1));
+    save.settings.music = false;
+    save.settings.bindings.rebind(Action::Reset, "Space");
+
+    let parsed = SaveData::parse(&save.to_ron().unwrap()).unwrap();
+    assert_eq!(parsed, save);
+}
+
+#[test]
+fn version_1_saves_are_upgraded() {
+    let save = SaveData::parse(V1).unwrap();
+    assert_eq!(save.version, SaveData::default().version);
+    assert!(save.progress.is_cleared(1));
+    assert_eq!(save.best[&0], best(12, 3, 0, 1));
+    assert!(!save.settings.music);
+    assert_eq!(save.settings.bindings, Bindings::default());
+}
+
+#[test]
+fn version_2_saves_are_upgraded() {
+    let save = SaveData::parse(V2).unwrap();
+    assert_eq!(save.version, SaveData::default().version);
+    assert_eq!(save.best[&0], best(14, 4, 9, 2));
+    assert!(save.settings.music);
+    assert_eq!(save.settings.bindings, Bindings::default());
+}
+
+#[test]
+fn version_3_saves_are_upgraded() {
+    let save = SaveData::parse(V3).unwrap();
+    assert_eq!(save.version, SaveData::default().version);
+    assert_eq!(save.best[&0], best(14, 4, 9, 2));
+    assert_eq!(save.settings.bindings.keys(Action::Reset), &["Space"]);
+}
+
+#[test]
+fn newer_saves_are_refused() {
+    assert!(SaveData::parse("(version: 99)").is_err());
+}
+
+#[test]
+fn best_results_are_kept_on_their_own() {
+    let score = |turns, runs, walls| Score {
+        turns,
+        runs,
+        required_runs: 3,
+        walls,
+    };
+    let mut save = SaveData::default();
+    save.record_clear(0, BestResult::new(score(20, 3, 10), 30));
+    save.record_clear(0, BestResult::new(score(15, 5, 12), 30));
+    assert_eq!(save.best[&0], best(15, 3, 10, 3));
+}
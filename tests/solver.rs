@@ -0,0 +1,83 @@
+use butterfly_effect::{
+    solve, solve_runs, Board, Direction, Event, Level, Move, Outlook, Position, Simulation, Tile,
+};
+
+fn board(grid: &str, turns: u32) -> Board {
+    let level = Level::parse(&format!("turns: {}\nruns: 1\n---\n{}", turns, grid)).unwrap();
+    Board::from_level(&level)
+}
+
+#[test]
+fn runs_stay_within_the_turn_budget() {
+    // Launching right and turning up at the end of the row takes two turns.
+    let board = board("  T\nS  ", 2);
+    assert_eq!(solve(&board, board.start(), 1), None);
+    assert_eq!(
+        solve(&board, board.start(), 2).map(|moves| moves.len()),
+        Some(2)
+    );
+}
+
+#[test]
+fn runs_take_the_fewest_turns() {
+    // Zigzagging up the stairs is as short, but takes more turns.
+    let board = board("  WT\n W  \nS   ", 5);
+    let moves = solve(&board, board.start(), 5).unwrap();
+    assert_eq!(
+        moves,
+        vec![
+            Move {
+                position: board.start(),
+                direction: Direction::Right,
+                tick: 0,
+            },
+            Move {
+                position: Position { x: 4, y: 1 },
+                direction: Direction::Up,
+                tick: 3,
+            },
+        ]
+    );
+}
+
+#[test]
+fn walls_of_earlier_runs_can_block_the_runs_left() {
+    // The only way to the target turns on the tile below it, which leaves a
+    // wall in the way of the next run.
+    let board = board(" WT\nS  ", 2);
+    let simulation = Simulation::new(board.start(), 2);
+    assert!(matches!(
+        solve_runs(&board, &simulation, 1),
+        Outlook::Solvable(runs) if runs.len() == 1
+    ));
+    assert_eq!(solve_runs(&board, &simulation, 2), Outlook::Unsolvable);
+}
+
+#[test]
+fn turning_on_the_start_leaves_no_wall() {
+    // The first run loops around and turns on the start into the target,
+    // the next one still gets there by steering left.
+    let mut board = board("    \nTS  ", 5);
+    let start = board.start();
+    let mut simulation = Simulation::new(start, 5);
+    let mut events = vec![];
+    for &direction in &[
+        Direction::Right,
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+        Direction::Left,
+    ] {
+        assert!(simulation.steer(direction));
+        events = simulation.step(&mut board);
+    }
+    assert!(events.contains(&Event::ReachedTarget(Position { x: 1, y: 1 })));
+    assert_eq!(board.tile(start), Tile::Empty);
+
+    simulation.restart();
+    assert!(matches!(
+        solve_runs(&board, &simulation, 1),
+        Outlook::Solvable(runs) if runs.len() == 1
+    ));
+    assert!(board.target_reachable(start));
+}